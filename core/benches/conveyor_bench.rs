@@ -0,0 +1,63 @@
+use criterion::*;
+use specs::prelude::*;
+use std::time::Duration;
+use vek::*;
+
+use minrusty::*;
+
+/// Width of the loops belts are laid out in.
+const LOOP: i32 = 50;
+
+/// Builds loops of belts, two tiles high, which carry an item per belt around
+/// forever.
+fn belts(count: i32) -> (World, ConveyorSystem) {
+    let mut world = World::new();
+    let mut system = ConveyorSystem::default();
+    System::setup(&mut system, &mut world);
+    world.register::<Tile>();
+
+    let rows = count / (LOOP * 2);
+    world.insert(Map::<Cell>::with_dimentions(Vec2::new(LOOP, rows * 2)));
+    world.insert(DeltaTime(Duration::from_secs_f32(1.0 / 60.0)));
+
+    for row in 0..rows {
+        for x in 0..LOOP {
+            let (bottom, top) = (row * 2, row * 2 + 1);
+            let right = if x == LOOP - 1 {
+                Direction::Up
+            } else {
+                Direction::Right
+            };
+            let left = if x == 0 {
+                Direction::Down
+            } else {
+                Direction::Left
+            };
+            for &(y, direction) in [(bottom, right), (top, left)].iter() {
+                let mut conveyor = Conveyor::new(direction);
+                conveyor.push(ItemId(0));
+                spawn_conveyor(&mut world, Vec2::new(x, y), conveyor);
+            }
+        }
+    }
+    (world, system)
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("conveyors");
+    group.warm_up_time(Duration::new(1, 0));
+    group.measurement_time(Duration::new(2, 0));
+
+    for &count in [1_000, 10_000, 100_000].iter() {
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("tick", count), &count, |b, &count| {
+            let (world, mut system) = belts(count);
+            b.iter(|| system.run_now(&world));
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
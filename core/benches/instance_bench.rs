@@ -0,0 +1,54 @@
+use criterion::*;
+use rand::Rng;
+use std::time::Duration;
+use vek::*;
+
+use minrusty::{Instance, Instances};
+
+fn instance(rng: &mut impl Rng) -> Instance {
+    let coordinate = Vec2::new(rng.gen_range(0, 256), rng.gen_range(0, 256));
+    Instance::tile(coordinate, rng.gen_range(0, 4), Rgba::white())
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instances");
+    group.warm_up_time(Duration::new(1, 0));
+    group.measurement_time(Duration::new(2, 0));
+
+    let mut rng = rand::thread_rng();
+
+    for &count in [1_000u32, 10_000, 100_000].iter() {
+        group.bench_with_input(BenchmarkId::new("build", count), &count, |b, &count| {
+            let mut instances = Instances::default();
+            b.iter(|| {
+                instances.clear();
+                for key in 0..count {
+                    instances.insert(key, instance(&mut rng));
+                }
+                black_box(instances.take_dirty())
+            });
+        });
+
+        // Only a few instances change between frames, which is the common case.
+        let mut instances = Instances::default();
+        for key in 0..count {
+            instances.insert(key, instance(&mut rng));
+        }
+        instances.take_dirty();
+
+        group.bench_with_input(BenchmarkId::new("update", count), &count, |b, &count| {
+            b.iter(|| {
+                for _ in 0..count / 100 {
+                    let key = rng.gen_range(0, count);
+                    instances.insert(key, instance(&mut rng));
+                }
+                black_box(instances.take_dirty())
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -2,9 +2,130 @@ use specs::prelude::*;
 use vek::*;
 
 use crate::components::*;
-use crate::game::{resources::Frame, Scene};
+use crate::construction::{site, BuildCosts, Core, Deconstructions};
+use crate::conveyor::Conveyor;
+use crate::crafting::Factory;
+use crate::drill::Drill;
+use crate::fluids::{FluidTank, Fluids, Pump};
+use crate::game::{resources::Frame, Input, Scene};
+use crate::health::{Armor, Health};
+use crate::items::Inventory;
 use crate::map::{Cell, Map};
+use crate::power::{Battery, Consumer, Generator, PowerNode};
 
+/// Amount of every item placed blocks hold.
+const BLOCK_CAPACITY: u32 = 10;
+
+/// Power generators produce every second.
+const GENERATOR_OUTPUT: f32 = 2.0;
+
+const BATTERY_CAPACITY: f32 = 50.0;
+const BATTERY_RATE: f32 = 2.0;
+
+/// Amount of fluid blocks hold, where pipes hold little, so fluids go through
+/// them quickly.
+const PIPE_CAPACITY: f32 = 10.0;
+const TANK_CAPACITY: f32 = 200.0;
+const FACTORY_FLUID_CAPACITY: f32 = 50.0;
+
+/// Amount of water pumps pump every second.
+const PUMP_RATE: f32 = 5.0;
+
+/// Amount of every item cores hold.
+pub const CORE_CAPACITY: u32 = 500;
+
+const CORE_HEALTH: f32 = 1000.0;
+const CORE_ARMOR: f32 = 5.0;
+
+/// Resource with the block which is placed on click.
+#[derive(Default)]
+pub struct BuildChoice {
+    pub name: Option<String>,
+
+    /// Rotation blocks are placed with, which is turned with the rotate key.
+    pub rotation: Rotation,
+}
+
+/// Tiles a block takes up, before it's rotated.
+pub fn footprint(name: &str) -> Footprint {
+    match name {
+        "drill" | "heavy-drill" | "factory" | "tank" | "core" => Footprint(Vec2::new(2, 2)),
+        _ => Footprint::default(),
+    }
+}
+
+/// Health of a finished block, where belts, pipes and poles are flimsy.
+pub fn health(name: &str) -> Health {
+    match name {
+        "conveyor" | "splitter" | "pipe" | "power-pole" => Health::new(50.0),
+        "core" => Health::new(CORE_HEALTH),
+        _ => Health::new(100.0),
+    }
+}
+
+/// Adds the components of a finished block to an entity.
+pub fn block<B: Builder>(
+    builder: B,
+    name: &str,
+    coordinate: Vec2<i32>,
+    rotation: Rotation,
+    fluids: &Fluids,
+) -> B {
+    let builder = builder
+        .with(Name(name.into()))
+        .with(Coordinate(coordinate))
+        .with(TextureIndex(None))
+        .with(Color(Rgba::white()))
+        .with(Tile)
+        .with(rotation)
+        .with(footprint(name))
+        .with(health(name));
+
+    // NOTE: belts only carry items, instead of holding them.
+    match name {
+        "conveyor" => builder.with(Conveyor::new(rotation.facing())),
+        "splitter" => builder.with(Conveyor::splitter(rotation.facing())),
+        "drill" => builder
+            .with(Drill::new(1))
+            .with(Inventory::new(BLOCK_CAPACITY)),
+        "heavy-drill" => builder
+            .with(Drill::new(2))
+            .with(Inventory::new(BLOCK_CAPACITY)),
+        "factory" => builder
+            .with(Factory::new(None))
+            .with(Inventory::new(BLOCK_CAPACITY))
+            .with(PowerNode)
+            .with(Consumer::new(0.0))
+            .with(FluidTank::new(FACTORY_FLUID_CAPACITY)),
+        "generator" => builder.with(PowerNode).with(Generator {
+            output: GENERATOR_OUTPUT,
+        }),
+        "battery" => builder
+            .with(PowerNode)
+            .with(Battery::new(BATTERY_CAPACITY, BATTERY_RATE)),
+        "power-pole" => builder.with(PowerNode),
+        "pipe" => builder.with(FluidTank::new(PIPE_CAPACITY).tinted()),
+        "tank" => builder.with(FluidTank::new(TANK_CAPACITY).tinted()),
+        // NOTE: pumps draw water out of the ground, wherever they are.
+        "pump" => {
+            let water = fluids.find("water").expect("Water is defined.");
+            builder
+                .with(FluidTank::new(PIPE_CAPACITY).tinted())
+                .with(Pump {
+                    fluid: water,
+                    rate: PUMP_RATE,
+                })
+        }
+        "core" => builder
+            .with(Core)
+            .with(Inventory::new(CORE_CAPACITY))
+            .with(Armor(CORE_ARMOR)),
+        _ => builder.with(Inventory::new(BLOCK_CAPACITY)),
+    }
+}
+
+/// System which places blocks where the cursor is clicked, and requests the
+/// deconstruction of blocks which are right clicked.
 #[derive(Default)]
 pub struct BuilderSystem;
 
@@ -15,27 +136,61 @@ impl<'a> System<'a> for BuilderSystem {
         Read<'a, Frame>,
         Read<'a, Map<Cell>>,
         Read<'a, Scene>,
+        Read<'a, Input>,
+        Read<'a, BuildChoice>,
+        Read<'a, Fluids>,
+        Read<'a, BuildCosts>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Deconstructions>,
         WriteStorage<'a, Color>,
     );
 
     fn run(
         &mut self,
         (
-            _entities,
+            entities,
             _frame,
             map,
             scene,
+            input,
+            choice,
+            fluids,
+            costs,
+            lazy,
+            mut deconstructions,
             mut colors,
             // ..
         ): Self::SystemData,
     ) {
+        // NOTE: the cursor is over the user interface.
+        if input.captured {
+            return;
+        }
+
         let cursor = scene.coordinate_cursor();
+        let rotation = choice.rotation;
+
+        let footprint = choice.name.as_deref().map(footprint).unwrap_or_default();
+        let free = footprint
+            .tiles(cursor, rotation)
+            .all(|tile| matches!(map.get(tile), Some(Cell { tile: None, .. })));
+
         if let Some(cell) = map.get(cursor) {
-            if let Some(entity) = cell.tile {
-                if let Some(ref mut color) = colors.get_mut(entity) {
-                    color.0 = Rgba::new(0.1, 0.1, 0.1, 0.1);
-                    // TODO: drop block here?
+            match (cell.tile, &choice.name) {
+                (Some(entity), _) if input.right_clicked => deconstructions.0.push(entity),
+                (Some(entity), _) => {
+                    if let Some(ref mut color) = colors.get_mut(entity) {
+                        color.0 = Rgba::new(0.1, 0.1, 0.1, 0.1);
+                    }
+                }
+                (None, Some(name)) if input.clicked && free => {
+                    let builder = lazy.create_entity(&entities);
+                    match costs.get(name) {
+                        Some(cost) => site(builder, name, cursor, rotation, cost).build(),
+                        None => block(builder, name, cursor, rotation, &fluids).build(),
+                    };
                 }
+                _ => {}
             }
         }
     }
@@ -1,83 +1,157 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
-#![allow(unused_variables)]
-
-use humantime::format_duration;
-use instant::Instant;
-use specs::prelude::*;
-#[cfg(feature = "nat")]
-use std::thread;
-use std::time::Duration;
-
-fn system_sleep(duration: Duration) {
-    #[cfg(feature = "nat")]
-    {
-        thread::sleep(duration);
-    }
+//! Frame pacing, which keeps the game from drawing more frames than it
+//! should, without taking up 100% of the thread while waiting.
 
-    #[cfg(feature = "web")]
-    {
-        // TODO: use web-sys to call setTimeout.
-        unimplemented!();
-    }
+use instant::{Duration, Instant};
+
+/// Measured rates of the event loop, averaged over about a second.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameRate {
+    /// Iterations of the event loop per second, which are about as many as
+    /// frames, unless the loop is woken up by input while waiting.
+    pub ticks: f32,
+
+    /// Frames drawn per second.
+    pub frames: f32,
 }
 
-/// Prevents the application from taking up 100% of the thread.
+/// Schedules frames at a target rate, and measures the rates at which the
+/// event loop ticks and frames are drawn.
 pub struct Clock {
-    start_time: Instant,
-    last_time: Instant,
-    pub ticks: u64,
+    /// Time between frames, which are drawn as often as possible when `None`.
+    target: Option<Duration>,
+    next_frame: Instant,
+
+    /// Ticks and frames counted since the start of the measurement.
+    start: Instant,
+    ticks: u32,
+    frames: u32,
+
+    rate: FrameRate,
 }
 
 impl Clock {
-    pub fn new() -> Self {
-        Self {
-            start_time: Instant::now(),
-            last_time: Instant::now(),
+    pub fn new(frame_cap: Option<f32>, now: Instant) -> Self {
+        let mut clock = Self {
+            target: None,
+            next_frame: now,
+            start: now,
             ticks: 0,
+            frames: 0,
+            rate: FrameRate::default(),
+        };
+        clock.set_frame_cap(frame_cap);
+        clock
+    }
+
+    pub fn set_frame_cap(&mut self, frame_cap: Option<f32>) {
+        self.target = frame_cap.map(|fps| Duration::from_secs_f32(1.0 / fps));
+    }
+
+    /// Counts an iteration of the event loop.
+    pub fn tick(&mut self, now: Instant) {
+        self.ticks += 1;
+        self.measure(now);
+    }
+
+    /// Returns when the next frame is due, or `None` if it's due now.
+    pub fn wait(&self, now: Instant) -> Option<Instant> {
+        match self.target {
+            Some(_) if now < self.next_frame => Some(self.next_frame),
+            _ => None,
         }
     }
 
-    /// Attempts to sleep about the target time.
-    pub fn tick(&mut self, target: Duration) {
-        let Self {
-            ref mut last_time,
-            ref mut ticks,
-            ref start_time,
-        } = self;
-
-        let now = loop {
-            let now = Instant::now();
-            let delta_since = now.duration_since(*last_time);
-            if delta_since < target {
-                let later = target - delta_since;
-                system_sleep(later);
+    /// Counts a frame drawn at `now`, and schedules the next one.
+    pub fn frame(&mut self, now: Instant) {
+        self.frames += 1;
+        if let Some(target) = self.target {
+            // NOTE: frames are scheduled from when the last one was due, so
+            // that waking up late doesn't lower the rate, unless it's so late
+            // that frames would be drawn back to back to catch up.
+            let next_frame = self.next_frame + target;
+            self.next_frame = if next_frame < now {
+                now + target
             } else {
-                break now;
-            }
-        };
-
-        *last_time = now;
-        *ticks += 1;
+                next_frame
+            };
+        }
+        self.measure(now);
+    }
 
-        use std::convert::TryInto;
+    pub fn rate(&self) -> FrameRate {
+        self.rate
+    }
 
-        if *ticks % 100 == 0 {
-            // println!(
-            //     "tick {:6} ({} tps -- {:>16} pt)",
-            //     *ticks,
-            //     *ticks / (now.duration_since(*start_time).as_secs() + 1),
-            //     format_duration(now.duration_since(*start_time) / (*ticks).try_into().unwrap()),
-            // );
+    fn measure(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.start).as_secs_f32();
+        if elapsed >= 1.0 {
+            self.rate = FrameRate {
+                ticks: self.ticks as f32 / elapsed,
+                frames: self.frames as f32 / elapsed,
+            };
+            self.start = now;
+            self.ticks = 0;
+            self.frames = 0;
         }
     }
 }
 
-/// Clock system, presumably designed to be thread local.
-struct Sys;
+/// Sends an event through `proxy` once the browser is about to repaint, which
+/// wakes up the event loop for the next frame.
+#[cfg(feature = "web")]
+pub fn request_animation_frame(proxy: &winit::event_loop::EventLoopProxy<()>) {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let proxy = proxy.clone();
+    let callback = Closure::once_into_js(move || {
+        // NOTE: the event loop only closes with the page.
+        let _ = proxy.send_event(());
+    });
+    web_sys::window()
+        .unwrap()
+        .request_animation_frame(callback.unchecked_ref())
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_cap_test() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut clock = Clock::new(Some(50.0), start);
 
-impl<'a> System<'a> for Sys {
-    type SystemData = ();
+        assert_eq!(clock.wait(start), None);
+        clock.frame(start);
+        assert_eq!(clock.wait(ms(10)), Some(ms(20)));
 
-    fn run(&mut self, _: ()) {}
+        // NOTE: a late frame doesn't push back the ones after it.
+        clock.frame(ms(25));
+        assert_eq!(clock.wait(ms(30)), Some(ms(40)));
+
+        // NOTE: but frames aren't drawn back to back after a long stall.
+        clock.frame(ms(200));
+        assert_eq!(clock.wait(ms(210)), Some(ms(220)));
+
+        clock.set_frame_cap(None);
+        assert_eq!(clock.wait(ms(210)), None);
+    }
+
+    #[test]
+    fn rate_test() {
+        let start = Instant::now();
+        let mut clock = Clock::new(None, start);
+        for i in 1..=100 {
+            let now = start + Duration::from_millis(i * 10);
+            clock.tick(now);
+            clock.tick(now);
+            clock.frame(now);
+        }
+
+        let FrameRate { ticks, frames } = clock.rate();
+        assert!((ticks - 200.0).abs() < 2.0);
+        assert!((frames - 100.0).abs() < 2.0);
+    }
 }
@@ -1,3 +1,5 @@
+use crate::render::{Align, Layer};
+
 use specs::prelude::*;
 use specs::Component;
 use vek::*;
@@ -22,21 +24,218 @@ pub struct Force(pub Vec2<f32>);
 #[derive(Component, Default)]
 pub struct Velocity(pub Vec2<f32>);
 
-/// Position in 3D space.
-#[derive(Component, Clone, Debug)]
+/// One of the four directions of the tile grid, where up is towards +y.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    /// Offset to the neighbouring tile in this direction.
+    pub fn offset(self) -> Vec2<i32> {
+        match self {
+            Direction::Up => Vec2::new(0, 1),
+            Direction::Right => Vec2::new(1, 0),
+            Direction::Down => Vec2::new(0, -1),
+            Direction::Left => Vec2::new(-1, 0),
+        }
+    }
+
+    pub fn clockwise(self) -> Self {
+        Self::ALL[(self as usize + 1) % 4]
+    }
+
+    pub fn counter_clockwise(self) -> Self {
+        Self::ALL[(self as usize + 3) % 4]
+    }
+
+    pub fn opposite(self) -> Self {
+        Self::ALL[(self as usize + 2) % 4]
+    }
+}
+
+/// Rotation of a block in quarter turns counter-clockwise, where unrotated
+/// blocks face right.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Component for Rotation {
+    /// Storage is flagged, so that the renderer turns the sprites of rotated blocks.
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl Rotation {
+    const ALL: [Rotation; 4] = [
+        Rotation::Deg0,
+        Rotation::Deg90,
+        Rotation::Deg180,
+        Rotation::Deg270,
+    ];
+
+    pub fn quarter_turns(self) -> u32 {
+        self as u32
+    }
+
+    pub fn counter_clockwise(self) -> Self {
+        Self::ALL[(self as usize + 1) % 4]
+    }
+
+    pub fn clockwise(self) -> Self {
+        Self::ALL[(self as usize + 3) % 4]
+    }
+
+    /// Direction a block with this rotation faces.
+    pub fn facing(self) -> Direction {
+        self.rotate(Direction::Right)
+    }
+
+    /// Turns a direction relative to an unrotated block into a direction on
+    /// the grid.
+    pub fn rotate(self, direction: Direction) -> Direction {
+        (0..self.quarter_turns()).fold(direction, |d, _| d.counter_clockwise())
+    }
+
+    /// Turns an offset from the origin of a block.
+    pub fn rotate_offset(self, offset: Vec2<i32>) -> Vec2<i32> {
+        (0..self.quarter_turns()).fold(offset, |o, _| Vec2::new(-o.y, o.x))
+    }
+}
+
+/// Size of a block in tiles, which covers the tiles up and to the right of
+/// it's coordinate before it's rotated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Footprint(pub Vec2<i32>);
+impl Component for Footprint {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl Default for Footprint {
+    fn default() -> Self {
+        Footprint(Vec2::one())
+    }
+}
+
+impl Footprint {
+    /// Tiles covered by a block at `coordinate`.
+    pub fn tiles(
+        self,
+        coordinate: Vec2<i32>,
+        rotation: Rotation,
+    ) -> impl Iterator<Item = Vec2<i32>> {
+        let size = self.0;
+        (0..size.y).flat_map(move |y| {
+            (0..size.x).map(move |x| coordinate + rotation.rotate_offset(Vec2::new(x, y)))
+        })
+    }
+
+    /// Center of the tiles covered by a block at `coordinate`.
+    pub fn center(self, coordinate: Vec2<i32>, rotation: Rotation) -> Vec2<f32> {
+        let corner = rotation.rotate_offset(self.0 - 1);
+        coordinate.numcast::<f32>().unwrap() + corner.numcast::<f32>().unwrap() / 2.0
+    }
+
+    /// Size of the covered tiles, along the axes of the grid.
+    pub fn rotated(self, rotation: Rotation) -> Vec2<i32> {
+        match rotation {
+            Rotation::Deg0 | Rotation::Deg180 => self.0,
+            Rotation::Deg90 | Rotation::Deg270 => self.0.yx(),
+        }
+    }
+}
+
+/// Set of sides of a block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sides(u8);
+
+impl Sides {
+    pub const NONE: Sides = Sides(0);
+    pub const ALL: Sides = Sides(0b1111);
+
+    pub fn new(directions: &[Direction]) -> Self {
+        Sides(directions.iter().fold(0, |bits, &d| bits | 1 << d as u8))
+    }
+
+    pub fn contains(self, direction: Direction) -> bool {
+        self.0 & 1 << direction as u8 != 0
+    }
+
+    pub fn rotate(self, rotation: Rotation) -> Self {
+        Sides(
+            self.iter()
+                .fold(0, |bits, d| bits | 1 << rotation.rotate(d) as u8),
+        )
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Direction> {
+        Direction::ALL
+            .iter()
+            .copied()
+            .filter(move |&d| self.contains(d))
+    }
+}
+
+/// Sides items go into and come out of a block, relative to the block facing
+/// right, which logic systems turn with the rotation of the block.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Io {
+    pub inputs: Sides,
+    pub outputs: Sides,
+}
+
+impl Io {
+    pub fn inputs(&self, rotation: Rotation) -> Sides {
+        self.inputs.rotate(rotation)
+    }
+
+    pub fn outputs(&self, rotation: Rotation) -> Sides {
+        self.outputs.rotate(rotation)
+    }
+}
+
+/// Position in 3D space, where z orders sprites within their render layer.
+#[derive(Clone, Debug)]
 pub struct Position(pub Vec3<f32>);
+impl Component for Position {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
 
 /// Position in tile space. Tile are 1 by 1 in size, so this component
 /// can't represent half a tile.
-#[derive(Component, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Coordinate(pub Vec2<i32>);
+impl Component for Coordinate {
+    /// Storage is flagged, so that the renderer only rebuilds moved sprites.
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
 
-/// Size of a tile.
-#[derive(Component, Clone, Debug)]
+/// Size of a sprite, in tiles or in pixels for sprites drawn in screen-space.
+#[derive(Clone, Debug)]
 pub struct Size(pub f32);
+impl Component for Size {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
 
-#[derive(Component, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Color(pub Rgba<f32>);
+impl Component for Color {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
 
 /// An internal name used to bundle assets together.
 pub struct Name(pub String);
@@ -50,8 +249,44 @@ impl Component for Name {
 #[derive(Component, Clone, Copy, Debug)]
 pub struct RenderId(pub Option<usize>);
 
-#[derive(Component, Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct TextureIndex(pub Option<u32>);
+impl Component for TextureIndex {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+/// Layer an entity is drawn in; tiles default to blocks, and anything else
+/// defaults to units.
+#[derive(Clone, Copy, Debug)]
+pub struct RenderLayer(pub Layer);
+impl Component for RenderLayer {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+/// Text drawn at the position of an entity, in it's color or black, and in
+/// the overlay unless it has a render layer.
+#[derive(Component, Clone, Debug)]
+pub struct Text {
+    pub content: String,
+
+    /// Height of a line, in tiles or in pixels for text drawn in screen-space.
+    pub size: f32,
+    pub align: Align,
+
+    /// Width after which lines are wrapped, in the same units as the size.
+    pub wrap: Option<f32>,
+}
+
+impl Text {
+    pub fn new(content: impl Into<String>, size: f32) -> Self {
+        Self {
+            content: content.into(),
+            size,
+            align: Align::default(),
+            wrap: None,
+        }
+    }
+}
 
 /// Component for controlling entities.
 pub struct Controller {
@@ -61,3 +296,39 @@ pub struct Controller {
 impl Component for Controller {
     type Storage = VecStorage<Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_test() {
+        assert_eq!(Rotation::Deg90.facing(), Direction::Up);
+        assert_eq!(Rotation::Deg270.facing(), Direction::Down);
+        assert_eq!(Rotation::Deg270.counter_clockwise(), Rotation::Deg0);
+
+        let sides = Sides::new(&[Direction::Left, Direction::Up]);
+        let turned = Sides::new(&[Direction::Down, Direction::Left]);
+        assert_eq!(sides.rotate(Rotation::Deg90), turned);
+    }
+
+    #[test]
+    fn footprint_test() {
+        let footprint = Footprint(Vec2::new(3, 2));
+        let origin = Vec2::new(10, 10);
+
+        let tiles: Vec<_> = footprint.tiles(origin, Rotation::Deg90).collect();
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.contains(&Vec2::new(10, 12)));
+        assert!(tiles.contains(&Vec2::new(9, 12)));
+        assert_eq!(footprint.rotated(Rotation::Deg90), Vec2::new(2, 3));
+        assert_eq!(
+            footprint.center(origin, Rotation::Deg90),
+            Vec2::new(9.5, 11.0)
+        );
+        assert_eq!(
+            footprint.center(origin, Rotation::Deg180),
+            Vec2::new(9.0, 9.5)
+        );
+    }
+}
@@ -0,0 +1,632 @@
+//! Construction of blocks, which are placed as sites that use up items out of
+//! the nearest core over their build time, and deconstruction, which refunds
+//! a share of what blocks cost into the nearest core.
+
+use crate::builder::{block, footprint};
+use crate::components::*;
+use crate::fluids::Fluids;
+use crate::items::{Inventory, ItemEvent, Items, Stack};
+use crate::map::{Cell, Map};
+use crate::state::DeltaTime;
+use crate::worldgen::WorldGen;
+use crate::Error;
+
+use serde::Deserialize;
+use shrev::EventChannel;
+use specs::prelude::*;
+use specs::Component;
+use std::collections::{BTreeMap, HashMap};
+use vek::*;
+
+/// Build costs, which are bundled with the game.
+static BLOCKS: &str = include_str!("../assets/blocks.json");
+
+/// Items the first core starts with.
+const STARTING_ITEMS: &[(&str, u32)] = &[
+    ("stone", 50),
+    ("iron-plate", 100),
+    ("copper-plate", 50),
+    ("gear", 40),
+];
+
+/// Color of construction sites, which are see-through until they're built.
+const SITE: Rgba<f32> = Rgba {
+    r: 1.0,
+    g: 1.0,
+    b: 1.0,
+    a: 0.35,
+};
+
+/// Seconds between items a site takes from the cores, which doesn't depend on
+/// the frame rate, so blocks take as long to build on every machine.
+const DELIVERY: f32 = 1.0 / 20.0;
+
+/// Most items a site takes in a single frame, after which it falls behind.
+const MAX_DELIVERIES: u32 = 8;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CostDef {
+    items: BTreeMap<String, u32>,
+    time: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlocksDef {
+    refund: f32,
+    costs: BTreeMap<String, CostDef>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildCost {
+    pub items: Vec<Stack>,
+
+    /// Seconds it takes to build the block, once every item is there.
+    pub time: f32,
+}
+
+/// Resource with what every block costs, where blocks without a cost are
+/// built instantly.
+#[derive(Clone, Debug)]
+pub struct BuildCosts {
+    costs: HashMap<String, BuildCost>,
+
+    /// Share of the cost which is refunded when a block is deconstructed.
+    pub refund: f32,
+}
+
+impl Default for BuildCosts {
+    fn default() -> Self {
+        Self::parse(BLOCKS, &Items::default()).expect("Bundled build costs are valid.")
+    }
+}
+
+impl BuildCosts {
+    /// Parses JSON build costs, where items must be defined.
+    pub fn parse(json: &str, items: &Items) -> Result<Self, Error> {
+        let error = |message| Error::DataError(format!("blocks: {}", message));
+        let def: BlocksDef = serde_json::from_str(json).map_err(|err| error(err.to_string()))?;
+        if def.refund < 0.0 || def.refund > 1.0 {
+            return Err(error("refund isn't between 0 and 1".into()));
+        }
+
+        let mut costs = HashMap::new();
+        for (name, cost) in def.costs {
+            if cost.time < 0.0 {
+                return Err(error(format!("block `{}` has a negative time", name)));
+            }
+            let mut stacks = Vec::with_capacity(cost.items.len());
+            for (item, &amount) in &cost.items {
+                let item = items.find(item).ok_or_else(|| {
+                    error(format!("block `{}` costs unknown item `{}`", name, item))
+                })?;
+                stacks.push(Stack::new(item, amount));
+            }
+            let cost = BuildCost {
+                items: stacks,
+                time: cost.time,
+            };
+            costs.insert(name, cost);
+        }
+        Ok(Self {
+            costs,
+            refund: def.refund,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BuildCost> {
+        self.costs.get(name)
+    }
+
+    /// Items refunded for deconstructing a finished block, rounded down.
+    pub fn refund(&self, name: &str) -> Vec<Stack> {
+        self.get(name)
+            .map(|cost| {
+                cost.items
+                    .iter()
+                    .map(|stack| Stack::new(stack.item, (stack.amount as f32 * self.refund) as u32))
+                    .filter(|stack| stack.amount > 0)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Block which holds the items blocks are built with, and takes refunds.
+#[derive(Component, Clone, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Core;
+
+/// Block which is being built, and becomes the block once it's done.
+#[derive(Component, Clone, Debug)]
+pub struct ConstructionSite {
+    /// Name of the block being built.
+    pub name: String,
+    cost: BuildCost,
+
+    /// Amount of every item of the cost which was used up so far.
+    used: Vec<u32>,
+    progress: f32,
+}
+
+impl ConstructionSite {
+    pub fn new(name: &str, cost: &BuildCost) -> Self {
+        Self {
+            name: name.into(),
+            cost: cost.clone(),
+            used: vec![0; cost.items.len()],
+            progress: 0.0,
+        }
+    }
+
+    /// Progress of the construction, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Items used up so far, which are refunded in full when the site is
+    /// deconstructed.
+    pub fn used(&self) -> Vec<Stack> {
+        self.cost
+            .items
+            .iter()
+            .zip(&self.used)
+            .map(|(stack, &used)| Stack::new(stack.item, used))
+            .filter(|stack| stack.amount > 0)
+            .collect()
+    }
+
+    /// Fraction of the cost which was used up so far.
+    fn supplied(&self) -> f32 {
+        let total: u32 = self.cost.items.iter().map(|stack| stack.amount).sum();
+        if total == 0 {
+            return 1.0;
+        }
+        self.used.iter().sum::<u32>() as f32 / total as f32
+    }
+
+    /// Next item of the cost which wasn't used up yet, by index.
+    fn missing(&self) -> Option<usize> {
+        self.cost
+            .items
+            .iter()
+            .zip(&self.used)
+            .position(|(stack, &used)| used < stack.amount)
+    }
+}
+
+/// Adds the components of a construction site to an entity, which looks like
+/// the block being built.
+pub fn site<B: Builder>(
+    builder: B,
+    name: &str,
+    coordinate: Vec2<i32>,
+    rotation: Rotation,
+    cost: &BuildCost,
+) -> B {
+    builder
+        .with(Name(name.into()))
+        .with(Coordinate(coordinate))
+        .with(TextureIndex(None))
+        .with(Color(SITE))
+        .with(Tile)
+        .with(rotation)
+        .with(footprint(name))
+        .with(ConstructionSite::new(name, cost))
+}
+
+/// Spawns the first core, as close to the center of the map as there is room
+/// between walls, with the items to start building with.
+pub fn spawn_core(world: &mut World, generator: &WorldGen) -> Option<Entity> {
+    let dimensions = world.read_resource::<Map<Cell>>().dimensions();
+    let center = dimensions / 2;
+    let footprint = footprint("core");
+    let fits = |coordinate: Vec2<i32>| {
+        footprint.tiles(coordinate, Rotation::Deg0).all(|tile| {
+            tile.x >= 0
+                && tile.y >= 0
+                && tile.x < dimensions.x
+                && tile.y < dimensions.y
+                && !generator.terrain(tile).wall
+        })
+    };
+
+    // NOTE: rings around the center are searched, from the closest one out.
+    let radius = dimensions.x.max(dimensions.y);
+    let coordinate = (0..radius).find_map(|ring| {
+        (-ring..=ring)
+            .flat_map(|y| (-ring..=ring).map(move |x| Vec2::new(x, y)))
+            .filter(|offset: &Vec2<i32>| offset.x.abs() == ring || offset.y.abs() == ring)
+            .map(|offset| center + offset)
+            .find(|&coordinate| fits(coordinate))
+    })?;
+
+    let fluids: Fluids = (*world.read_resource::<Fluids>()).clone();
+    let core = block(
+        world.create_entity(),
+        "core",
+        coordinate,
+        Rotation::Deg0,
+        &fluids,
+    )
+    .build();
+
+    let items = world.read_resource::<Items>();
+    let stacks: Vec<_> = STARTING_ITEMS
+        .iter()
+        .filter_map(|&(name, amount)| Some(Stack::new(items.find(name)?, amount)))
+        .collect();
+    if let Some(inventory) = world.write_storage::<Inventory>().get_mut(core) {
+        inventory.insert(&stacks);
+    }
+    Some(core)
+}
+
+/// Core closest to a coordinate out of those `accepts` is true for, where
+/// cores without a coordinate come last.
+fn nearest_core(
+    entities: &Entities,
+    cores: &ReadStorage<Core>,
+    coordinates: &ReadStorage<Coordinate>,
+    inventories: &WriteStorage<Inventory>,
+    at: Option<Vec2<i32>>,
+    accepts: impl Fn(&Inventory) -> bool,
+) -> Option<Entity> {
+    (entities, cores, inventories, coordinates.maybe())
+        .join()
+        .filter(|(_, _, inventory, _)| accepts(inventory))
+        .min_by_key(|(_, _, _, coordinate)| match (at, coordinate) {
+            (Some(at), Some(coordinate)) => (coordinate.0 - at).map(|x| x * x).sum(),
+            (None, Some(_)) => 0,
+            (_, None) => i32::MAX,
+        })
+        .map(|(core, ..)| core)
+}
+
+/// Resource of the blocks which are deconstructed this frame.
+#[derive(Default)]
+pub struct Deconstructions(pub Vec<Entity>);
+
+/// System which builds construction sites out of the items in cores, and
+/// deconstructs blocks into refunds.
+#[derive(Default)]
+pub struct ConstructionSystem {
+    /// Time which no items were taken for yet.
+    accumulator: f32,
+}
+
+impl<'a> System<'a> for ConstructionSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, BuildCosts>,
+        Read<'a, Fluids>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Deconstructions>,
+        Write<'a, EventChannel<ItemEvent>>,
+        WriteStorage<'a, ConstructionSite>,
+        WriteStorage<'a, Inventory>,
+        ReadStorage<'a, Core>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Rotation>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            costs,
+            fluids,
+            lazy,
+            mut deconstructions,
+            mut events,
+            mut sites,
+            mut inventories,
+            cores,
+            names,
+            coordinates,
+            rotations,
+        ): Self::SystemData,
+    ) {
+        let dt = delta.0.as_secs_f32();
+        self.accumulator += dt;
+        let deliveries = ((self.accumulator / DELIVERY) as u32).min(MAX_DELIVERIES);
+        self.accumulator -= deliveries as f32 * DELIVERY;
+        self.accumulator = self.accumulator.min(DELIVERY);
+
+        // NOTE: cores can't be deconstructed, so there's always somewhere to
+        // build from, and neither can blocks without a cost, like the walls
+        // the world is generated with.
+        let mut removed = BitSet::new();
+        for entity in deconstructions.0.drain(..) {
+            if !entities.is_alive(entity) || cores.contains(entity) {
+                continue;
+            }
+            let refund = match (sites.get(entity), names.get(entity)) {
+                (Some(site), _) => site.used(),
+                (None, Some(name)) if costs.get(&name.0).is_some() => costs.refund(&name.0),
+                _ => continue,
+            };
+
+            // NOTE: refunds which don't fit in any core are lost.
+            let at = coordinates.get(entity).map(|coordinate| coordinate.0);
+            for stack in refund {
+                let core =
+                    nearest_core(&entities, &cores, &coordinates, &inventories, at, |core| {
+                        core.can_insert(&[stack])
+                    });
+                if let Some(core) = core {
+                    inventories.get_mut(core).unwrap().insert(&[stack]);
+                    events.single_write(ItemEvent {
+                        stack,
+                        from: None,
+                        to: Some(core),
+                    });
+                }
+            }
+
+            // NOTE: the mapping system frees the tiles once the block is gone.
+            removed.add(entity.id());
+            let _ = entities.delete(entity);
+        }
+
+        // NOTE: deleted entities are only gone after the world is maintained.
+        for (entity, site, coordinate, rotation, _) in (
+            &entities,
+            &mut sites,
+            &coordinates,
+            rotations.maybe(),
+            !&removed,
+        )
+            .join()
+        {
+            // NOTE: a single item is used up every delivery, and construction
+            // only gets as far as the items used up so far pay for.
+            for _ in 0..deliveries {
+                let index = match site.missing() {
+                    Some(index) => index,
+                    None => break,
+                };
+                let item = site.cost.items[index].item;
+                let stack = Stack::new(item, 1);
+                let at = Some(coordinate.0);
+                let core =
+                    nearest_core(&entities, &cores, &coordinates, &inventories, at, |core| {
+                        core.can_remove(&[stack])
+                    });
+                let core = match core {
+                    Some(core) => core,
+                    None => break,
+                };
+                inventories.get_mut(core).unwrap().remove(&[stack]);
+                site.used[index] += 1;
+                events.single_write(ItemEvent {
+                    stack,
+                    from: Some(core),
+                    to: None,
+                });
+            }
+
+            let step = if site.cost.time > 0.0 {
+                dt / site.cost.time
+            } else {
+                1.0
+            };
+            site.progress = (site.progress + step).min(site.supplied());
+            if site.progress >= 1.0 {
+                let rotation = rotation.copied().unwrap_or_default();
+                let builder = lazy.create_entity(&entities);
+                block(builder, &site.name, coordinate.0, rotation, &fluids).build();
+                let _ = entities.delete(entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::Health;
+    use instant::Duration;
+
+    fn world() -> (World, ConstructionSystem, Entity) {
+        let mut world = World::new();
+        let mut system = ConstructionSystem::default();
+        System::setup(&mut system, &mut world);
+        world.register::<TextureIndex>();
+        world.register::<Color>();
+        world.register::<Tile>();
+        world.register::<Footprint>();
+        world.register::<Health>();
+        world.insert(DeltaTime(Duration::from_millis(100)));
+        world.insert(Items::default());
+
+        let stone = world.read_resource::<Items>().find("stone").unwrap();
+        let mut inventory = Inventory::new(10);
+        inventory.insert(&[Stack::new(stone, 1)]);
+        let core = world.create_entity().with(Core).with(inventory).build();
+        (world, system, core)
+    }
+
+    fn stone(world: &World, core: Entity) -> u32 {
+        let stone = world.read_resource::<Items>().find("stone").unwrap();
+        let inventories = world.read_storage::<Inventory>();
+        inventories.get(core).unwrap().count(stone)
+    }
+
+    #[test]
+    fn construction_test() {
+        let (mut world, mut system, core) = world();
+        let cost = world
+            .read_resource::<BuildCosts>()
+            .get("a")
+            .unwrap()
+            .clone();
+        let coordinate = Vec2::new(3, 3);
+        let entity = site(
+            world.create_entity(),
+            "a",
+            coordinate,
+            Rotation::Deg0,
+            &cost,
+        )
+        .build();
+
+        system.run_now(&world);
+        world.maintain();
+        assert_eq!(stone(&world, core), 0);
+        let progress = world
+            .read_storage::<ConstructionSite>()
+            .get(entity)
+            .unwrap()
+            .progress();
+        assert!(progress > 0.0 && progress < 1.0);
+
+        for _ in 0..5 {
+            system.run_now(&world);
+            world.maintain();
+        }
+        assert!(!world.is_alive(entity));
+        let names = world.read_storage::<Name>();
+        let coordinates = world.read_storage::<Coordinate>();
+        let inventories = world.read_storage::<Inventory>();
+        let built = (&names, &coordinates, &inventories)
+            .join()
+            .any(|(name, c, _)| name.0 == "a" && c.0 == coordinate);
+        assert!(built);
+    }
+
+    /// Progress of a site which was built for half a second, at frames of `dt`.
+    fn progress_after(dt: Duration) -> f32 {
+        let (mut world, mut system, core) = world();
+        let stone = world.read_resource::<Items>().find("stone").unwrap();
+        let mut inventory = Inventory::new(20);
+        inventory.insert(&[Stack::new(stone, 20)]);
+        world
+            .write_storage::<Inventory>()
+            .insert(core, inventory)
+            .unwrap();
+        world.insert(DeltaTime(dt));
+
+        let cost = BuildCost {
+            items: vec![Stack::new(stone, 20)],
+            time: 1.0,
+        };
+        let entity = site(
+            world.create_entity(),
+            "a",
+            Vec2::new(1, 1),
+            Rotation::Deg0,
+            &cost,
+        )
+        .build();
+
+        let frames = (Duration::from_millis(500).as_micros() / dt.as_micros()) as usize;
+        for _ in 0..frames {
+            system.run_now(&world);
+            world.maintain();
+        }
+        let sites = world.read_storage::<ConstructionSite>();
+        sites.get(entity).unwrap().progress()
+    }
+
+    #[test]
+    fn construction_frame_rate_test() {
+        // NOTE: items are taken at a fixed rate, so a site is as far along at
+        // any frame rate, give or take a single item.
+        let slow = progress_after(Duration::from_millis(100));
+        let fast = progress_after(Duration::from_millis(10));
+        assert!(slow > 0.0 && slow < 1.0);
+        assert!((slow - fast).abs() <= 1.0 / 20.0 + 1e-4);
+    }
+
+    #[test]
+    fn deconstruction_test() {
+        let (mut world, mut system, core) = world();
+        let mut costs = world.write_resource::<BuildCosts>();
+        let stone_id = world.read_resource::<Items>().find("stone").unwrap();
+        let cost = BuildCost {
+            items: vec![Stack::new(stone_id, 4)],
+            time: 1.0,
+        };
+        costs.costs.insert("a".into(), cost.clone());
+        drop(costs);
+
+        // NOTE: sites refund what they used up, and blocks a share of it.
+        let unfinished = site(
+            world.create_entity(),
+            "a",
+            Vec2::new(1, 1),
+            Rotation::Deg0,
+            &cost,
+        )
+        .build();
+        system.run_now(&world);
+        assert_eq!(stone(&world, core), 0);
+        world.write_resource::<Deconstructions>().0.push(unfinished);
+        system.run_now(&world);
+        world.maintain();
+        assert!(!world.is_alive(unfinished));
+        assert_eq!(stone(&world, core), 1);
+
+        let fluids = Fluids::default();
+        let finished = block(
+            world.create_entity(),
+            "a",
+            Vec2::new(2, 2),
+            Rotation::Deg0,
+            &fluids,
+        )
+        .build();
+        let wall = world.create_entity().with(Name("wall".into())).build();
+        world.write_resource::<Deconstructions>().0.push(finished);
+        world.write_resource::<Deconstructions>().0.push(core);
+        world.write_resource::<Deconstructions>().0.push(wall);
+        system.run_now(&world);
+        world.maintain();
+        assert!(!world.is_alive(finished));
+        assert!(world.is_alive(wall));
+        assert_eq!(stone(&world, core), 3);
+    }
+
+    #[test]
+    fn nearest_core_test() {
+        let (mut world, mut system, first) = world();
+        let stone_id = world.read_resource::<Items>().find("stone").unwrap();
+        let cores: Vec<_> = [Vec2::new(0, 0), Vec2::new(10, 10)]
+            .iter()
+            .map(|&coordinate| {
+                let mut inventory = Inventory::new(10);
+                inventory.insert(&[Stack::new(stone_id, 1)]);
+                world
+                    .create_entity()
+                    .with(Core)
+                    .with(Coordinate(coordinate))
+                    .with(inventory)
+                    .build()
+            })
+            .collect();
+        let cost = world
+            .read_resource::<BuildCosts>()
+            .get("a")
+            .unwrap()
+            .clone();
+        site(
+            world.create_entity(),
+            "a",
+            Vec2::new(8, 9),
+            Rotation::Deg0,
+            &cost,
+        )
+        .build();
+
+        system.run_now(&world);
+        world.maintain();
+        assert_eq!(stone(&world, first), 1);
+        assert_eq!(stone(&world, cores[0]), 1);
+        assert_eq!(stone(&world, cores[1]), 0);
+    }
+}
@@ -0,0 +1,422 @@
+//! Conveyor belts, which carry items from tile to tile in the direction they
+//! face, and hand them over to the belts or inventories in front of them.
+
+use crate::components::*;
+use crate::items::{Inventory, ItemEvent, ItemId, Stack};
+use crate::map::{Cell, Map};
+use crate::state::DeltaTime;
+
+use shrev::EventChannel;
+use specs::prelude::*;
+use specs::Component;
+use std::collections::VecDeque;
+use vek::*;
+
+/// Tiles per second items move on belts.
+pub const BELT_SPEED: f32 = 2.0;
+
+/// Distance between items on belts, in tiles.
+pub const BELT_SPACING: f32 = 0.25;
+
+/// Longest step belts take at once, so that a slow frame doesn't make items
+/// skip their spacing.
+const MAX_STEP: f32 = 0.1;
+
+/// Item on a belt, at a distance from the start of the belt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeltItem {
+    pub item: ItemId,
+    pub progress: f32,
+}
+
+/// Belt which moves items towards the tile it faces, where items queue up
+/// behind each other when they can't leave it.
+#[derive(Component, Clone, Debug)]
+pub struct Conveyor {
+    pub direction: Direction,
+
+    /// Tiles per second items move.
+    pub speed: f32,
+
+    /// Distance items keep between each other, in tiles.
+    pub spacing: f32,
+
+    /// Whether items are split between the front and both sides of the belt,
+    /// instead of only leaving through the front.
+    pub splitter: bool,
+
+    /// Output the next item tries first, for splitters.
+    next_output: usize,
+
+    /// Direction the last item came in from, which lets belts merging into
+    /// this one take turns.
+    last_input: Option<Direction>,
+
+    /// Items from the furthest along to the last one on.
+    items: VecDeque<BeltItem>,
+}
+
+impl Conveyor {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            speed: BELT_SPEED,
+            spacing: BELT_SPACING,
+            splitter: false,
+            next_output: 0,
+            last_input: None,
+            items: VecDeque::new(),
+        }
+    }
+
+    pub fn splitter(direction: Direction) -> Self {
+        Self {
+            splitter: true,
+            ..Self::new(direction)
+        }
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &BeltItem> {
+        self.items.iter()
+    }
+
+    /// Whether an item fits at the start of the belt.
+    pub fn has_room(&self) -> bool {
+        match self.items.back() {
+            Some(last) => last.progress >= self.spacing,
+            None => true,
+        }
+    }
+
+    /// Puts an item at the start of the belt, if it fits.
+    pub fn push(&mut self, item: ItemId) -> bool {
+        if !self.has_room() {
+            return false;
+        }
+        self.items.push_back(BeltItem {
+            item,
+            progress: 0.0,
+        });
+        true
+    }
+
+    /// Item which reached the end of the belt.
+    fn ready(&self) -> Option<ItemId> {
+        self.items
+            .front()
+            .filter(|first| first.progress >= 1.0)
+            .map(|first| first.item)
+    }
+
+    /// Moves items `dt` seconds along, up to `limit` or the item in front of
+    /// them.
+    fn advance(&mut self, dt: f32, mut limit: f32) {
+        let step = self.speed * dt;
+        for item in self.items.iter_mut() {
+            item.progress = (item.progress + step).min(limit).max(item.progress);
+            limit = item.progress - self.spacing;
+        }
+    }
+
+    /// Directions items leave through, in the order they're tried.
+    fn outputs(&self) -> impl Iterator<Item = Direction> {
+        let direction = self.direction;
+        let outputs = [
+            direction,
+            direction.clockwise(),
+            direction.counter_clockwise(),
+        ];
+        let (count, start) = if self.splitter {
+            (3, self.next_output)
+        } else {
+            (1, 0)
+        };
+        (0..count).map(move |i| outputs[(start + i) % count])
+    }
+
+    /// Positions of items in world space, where items enter the belt at the
+    /// edge it faces away from.
+    pub fn positions(
+        &self,
+        coordinate: Vec2<i32>,
+    ) -> impl Iterator<Item = (Vec2<f32>, ItemId)> + '_ {
+        let center = coordinate.numcast::<f32>().unwrap();
+        let offset = self.direction.offset().numcast::<f32>().unwrap();
+        self.items.iter().map(move |item| {
+            let position = center + offset * (item.progress - 0.5);
+            (position, item.item)
+        })
+    }
+}
+
+/// Creates a belt on an empty tile.
+pub fn spawn_conveyor(world: &mut World, coordinate: Vec2<i32>, conveyor: Conveyor) -> Entity {
+    let entity = world
+        .create_entity()
+        .with(Coordinate(coordinate))
+        .with(Tile)
+        .with(conveyor)
+        .build();
+    if let Some(cell) = world.write_resource::<Map<Cell>>().get_mut(coordinate) {
+        cell.tile = Some(entity);
+    }
+    entity
+}
+
+/// System which moves items along belts, and from belts into whatever is in
+/// front of them.
+#[derive(Default)]
+pub struct ConveyorSystem {
+    /// Furthest items can move on belts which are about to reach a full belt,
+    /// by entity id, in the order belts are joined.
+    limits: Vec<(u32, f32)>,
+
+    /// Belts with an item at their end.
+    ready: Vec<(Entity, Vec2<i32>)>,
+}
+
+impl<'a> System<'a> for ConveyorSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, Map<Cell>>,
+        Write<'a, EventChannel<ItemEvent>>,
+        WriteStorage<'a, Conveyor>,
+        WriteStorage<'a, Inventory>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Io>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            map,
+            mut events,
+            mut conveyors,
+            mut inventories,
+            coordinates,
+            rotations,
+            ios,
+        ): Self::SystemData,
+    ) {
+        let Self { limits, ready } = self;
+        let dt = delta.0.as_secs_f32().min(MAX_STEP);
+
+        // NOTE: items wait at the end of a belt until the next one has room,
+        // so they stop short of it's last item, instead of overlapping with it.
+        limits.clear();
+        for (entity, conveyor, coordinate) in (&entities, &conveyors, &coordinates).join() {
+            let first = match conveyor.items.front() {
+                Some(first) if !conveyor.splitter => first.progress,
+                _ => continue,
+            };
+            if first + conveyor.speed * dt < 1.0 - conveyor.spacing {
+                continue;
+            }
+
+            let next = map
+                .tile(coordinate.0 + conveyor.direction.offset())
+                .and_then(|next| conveyors.get(next));
+            let last = next
+                .filter(|next| next.direction != conveyor.direction.opposite())
+                .and_then(|next| next.items.back());
+            if let Some(last) = last {
+                let limit = 1.0 + last.progress - conveyor.spacing;
+                limits.push((entity.id(), limit.min(1.0)));
+            }
+        }
+
+        ready.clear();
+        let mut limits = limits.iter().peekable();
+        for (entity, conveyor, coordinate) in (&entities, &mut conveyors, &coordinates).join() {
+            let limit = match limits.peek() {
+                Some(&&(id, limit)) if id == entity.id() => {
+                    limits.next();
+                    limit
+                }
+                _ => 1.0,
+            };
+            conveyor.advance(dt, limit);
+            if conveyor.ready().is_some() {
+                ready.push((entity, coordinate.0));
+            }
+        }
+
+        // NOTE: the first pass skips belts which fed the same belt last, so
+        // that belts which merge into another one take turns.
+        for &fair in [true, false].iter() {
+            for &(entity, coordinate) in ready.iter() {
+                let conveyor = conveyors.get(entity).unwrap();
+                let item = match conveyor.ready() {
+                    Some(item) => item,
+                    None => continue,
+                };
+
+                let mut delivered = None;
+                for (i, direction) in conveyor.outputs().enumerate() {
+                    let target = match map.tile(coordinate + direction.offset()) {
+                        Some(target) => target,
+                        None => continue,
+                    };
+
+                    if let Some(next) = conveyors.get_mut(target) {
+                        // NOTE: belts facing each other would pass items back
+                        // and forth forever.
+                        let skipped = fair && next.last_input == Some(direction);
+                        if next.direction != direction.opposite() && !skipped && next.push(item) {
+                            next.last_input = Some(direction);
+                            delivered = Some(i);
+                            break;
+                        }
+                    } else if let Some(inventory) = inventories.get_mut(target) {
+                        // NOTE: blocks with sides only take items through their inputs.
+                        let rotation = rotations.get(target).copied().unwrap_or_default();
+                        let side = direction.opposite();
+                        if let Some(io) = ios.get(target) {
+                            if !io.inputs(rotation).contains(side) {
+                                continue;
+                            }
+                        }
+
+                        let stack = Stack::new(item, 1);
+                        if inventory.insert(&[stack]) {
+                            events.single_write(ItemEvent {
+                                stack,
+                                from: Some(entity),
+                                to: Some(target),
+                            });
+                            delivered = Some(i);
+                            break;
+                        }
+                    }
+                }
+
+                // NOTE: items which can't leave stay at the end of the belt, and
+                // hold back the items behind them.
+                if let Some(i) = delivered {
+                    let conveyor = conveyors.get_mut(entity).unwrap();
+                    conveyor.items.pop_front();
+                    if conveyor.splitter {
+                        conveyor.next_output = (conveyor.next_output + i + 1) % 3;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    const ORE: ItemId = ItemId(0);
+
+    fn world() -> (World, ConveyorSystem) {
+        let mut world = World::new();
+        let mut system = ConveyorSystem::default();
+        System::setup(&mut system, &mut world);
+        world.register::<Tile>();
+        world.insert(DeltaTime(Duration::from_millis(50)));
+        (world, system)
+    }
+
+    fn belt(world: &mut World, x: i32, y: i32, direction: Direction) -> Entity {
+        spawn_conveyor(world, Vec2::new(x, y), Conveyor::new(direction))
+    }
+
+    fn chest(world: &mut World, x: i32, y: i32, capacity: u32) -> Entity {
+        let coordinate = Vec2::new(x, y);
+        let entity = world
+            .create_entity()
+            .with(Coordinate(coordinate))
+            .with(Inventory::new(capacity))
+            .build();
+        let mut map = world.write_resource::<Map<Cell>>();
+        map.get_mut(coordinate).unwrap().tile = Some(entity);
+        entity
+    }
+
+    fn push(world: &World, entity: Entity) -> bool {
+        let mut conveyors = world.write_storage::<Conveyor>();
+        conveyors.get_mut(entity).unwrap().push(ORE)
+    }
+
+    fn count(world: &World, entity: Entity) -> u32 {
+        let inventories = world.read_storage::<Inventory>();
+        inventories.get(entity).unwrap().count(ORE)
+    }
+
+    #[test]
+    fn back_pressure_test() {
+        let (mut world, mut system) = world();
+        let belts: Vec<_> = (0..3)
+            .map(|x| belt(&mut world, x, 0, Direction::Right))
+            .collect();
+        let chest = chest(&mut world, 3, 0, 5);
+
+        for _ in 0..200 {
+            push(&world, belts[0]);
+            system.run_now(&world);
+        }
+
+        // NOTE: the chest is full, so the belts fill up, and items keep their
+        // spacing across belts too.
+        assert_eq!(count(&world, chest), 5);
+        let conveyors = world.read_storage::<Conveyor>();
+        let mut positions = Vec::new();
+        for (x, &belt) in belts.iter().enumerate() {
+            let conveyor = conveyors.get(belt).unwrap();
+            assert!(conveyor.items().count() >= 4);
+            positions.extend(conveyor.positions(Vec2::new(x as i32, 0)).map(|(p, _)| p.x));
+        }
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for pair in positions.windows(2) {
+            assert!(pair[1] - pair[0] >= BELT_SPACING - 1e-4);
+        }
+    }
+
+    #[test]
+    fn merge_test() {
+        let (mut world, mut system) = world();
+        let left = belt(&mut world, 1, 2, Direction::Right);
+        let below = belt(&mut world, 2, 1, Direction::Up);
+        belt(&mut world, 2, 2, Direction::Right);
+        let chest = chest(&mut world, 3, 2, 1000);
+
+        let mut pushed = [0, 0];
+        for _ in 0..400 {
+            pushed[0] += push(&world, left) as i32;
+            pushed[1] += push(&world, below) as i32;
+            system.run_now(&world);
+        }
+
+        assert!(count(&world, chest) > 100);
+        assert!((pushed[0] - pushed[1]).abs() <= 2);
+    }
+
+    #[test]
+    fn split_test() {
+        let (mut world, mut system) = world();
+        let input = belt(&mut world, 1, 2, Direction::Right);
+        let splitter = Conveyor::splitter(Direction::Right);
+        spawn_conveyor(&mut world, Vec2::new(2, 2), splitter);
+        let chests = [
+            chest(&mut world, 3, 2, 1000),
+            chest(&mut world, 2, 3, 1000),
+            chest(&mut world, 2, 1, 1000),
+        ];
+
+        for _ in 0..200 {
+            push(&world, input);
+            system.run_now(&world);
+        }
+
+        let counts: Vec<_> = chests.iter().map(|&chest| count(&world, chest)).collect();
+        let (min, max) = (*counts.iter().min().unwrap(), *counts.iter().max().unwrap());
+        assert!(min > 10 && max - min <= 1, "{:?}", counts);
+    }
+}
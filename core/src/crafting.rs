@@ -0,0 +1,435 @@
+//! Recipes, which are defined as data, and the factories which craft them out
+//! of the items in their inventory.
+
+use crate::components::*;
+use crate::fluids::{FluidId, FluidTank, Fluids};
+use crate::items::{Inventory, ItemEvent, ItemId, Items, Stack};
+use crate::power::Consumer;
+use crate::state::DeltaTime;
+use crate::Error;
+
+use serde::Deserialize;
+use shrev::EventChannel;
+use specs::prelude::*;
+use specs::Component;
+use std::collections::BTreeMap;
+use vek::*;
+
+/// Recipe definitions, which are bundled with the game.
+static RECIPES: &str = include_str!("../assets/recipes.json");
+
+/// Tint of a factory which is about to finish crafting, where idle factories
+/// aren't tinted.
+const CRAFTING: Rgba<f32> = Rgba {
+    r: 1.0,
+    g: 0.6,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Index of a recipe definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecipeId(pub u16);
+
+/// Recipe as it's written, with items by name.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeDef {
+    name: String,
+    inputs: BTreeMap<String, u32>,
+    outputs: BTreeMap<String, u32>,
+    #[serde(default)]
+    fluids: BTreeMap<String, f32>,
+    time: f32,
+    #[serde(default)]
+    power: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Recipe {
+    pub name: String,
+    pub inputs: Vec<Stack>,
+    pub outputs: Vec<Stack>,
+
+    /// Fluids used up along with the inputs, out of the factory's tank.
+    pub fluids: Vec<(FluidId, f32)>,
+
+    /// Seconds it takes to craft the recipe once.
+    pub time: f32,
+
+    /// Power used while crafting.
+    pub power: f32,
+}
+
+/// Resource of every recipe definition.
+#[derive(Clone, Debug)]
+pub struct Recipes {
+    recipes: Vec<Recipe>,
+}
+
+impl Default for Recipes {
+    fn default() -> Self {
+        Self::parse(RECIPES, &Items::default(), &Fluids::default())
+            .expect("Bundled recipes are valid.")
+    }
+}
+
+impl Recipes {
+    /// Parses a JSON list of recipe definitions, where names must be unique,
+    /// items and fluids must be defined, and no item may be crafted out of
+    /// itself.
+    pub fn parse(json: &str, items: &Items, fluids: &Fluids) -> Result<Self, Error> {
+        let error = |message| Error::DataError(format!("recipes: {}", message));
+        let defs: Vec<RecipeDef> =
+            serde_json::from_str(json).map_err(|err| error(err.to_string()))?;
+        if defs.len() > u16::MAX as usize {
+            return Err(error("too many recipes".into()));
+        }
+
+        let mut recipes = Vec::with_capacity(defs.len());
+        for (i, def) in defs.iter().enumerate() {
+            if def.name.is_empty() {
+                return Err(error(format!("recipe {} has no name", i)));
+            }
+            if defs[..i].iter().any(|other| other.name == def.name) {
+                return Err(error(format!("recipe `{}` is defined twice", def.name)));
+            }
+            if def.outputs.is_empty() {
+                return Err(error(format!("recipe `{}` has no outputs", def.name)));
+            }
+            if def.time <= 0.0 || def.power < 0.0 {
+                return Err(error(format!(
+                    "recipe `{}` has an invalid time or power",
+                    def.name
+                )));
+            }
+
+            let stacks = |stacks: &BTreeMap<String, u32>| {
+                stacks
+                    .iter()
+                    .map(|(name, &amount)| match items.find(name) {
+                        Some(item) => Ok(Stack::new(item, amount)),
+                        None => Err(error(format!(
+                            "recipe `{}` uses unknown item `{}`",
+                            def.name, name
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            let mut used = Vec::with_capacity(def.fluids.len());
+            for (name, &amount) in &def.fluids {
+                let fluid = fluids.find(name).ok_or_else(|| {
+                    error(format!(
+                        "recipe `{}` uses unknown fluid `{}`",
+                        def.name, name
+                    ))
+                })?;
+                if amount <= 0.0 {
+                    return Err(error(format!("recipe `{}` uses no `{}`", def.name, name)));
+                }
+                used.push((fluid, amount));
+            }
+            recipes.push(Recipe {
+                name: def.name.clone(),
+                inputs: stacks(&def.inputs)?,
+                outputs: stacks(&def.outputs)?,
+                fluids: used,
+                time: def.time,
+                power: def.power,
+            });
+        }
+
+        if let Some(item) = cycle(&recipes) {
+            let name = &items.get(item).name;
+            return Err(error(format!("item `{}` is crafted out of itself", name)));
+        }
+        Ok(Self { recipes })
+    }
+
+    pub fn find(&self, name: &str) -> Option<RecipeId> {
+        self.recipes
+            .iter()
+            .position(|recipe| recipe.name == name)
+            .map(|i| RecipeId(i as u16))
+    }
+
+    pub fn get(&self, recipe: RecipeId) -> &Recipe {
+        &self.recipes[recipe.0 as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RecipeId, &Recipe)> {
+        self.recipes
+            .iter()
+            .enumerate()
+            .map(|(i, recipe)| (RecipeId(i as u16), recipe))
+    }
+}
+
+/// Item which is crafted out of itself through some chain of recipes, found
+/// with a depth first search from every item to the items crafted out of it.
+fn cycle(recipes: &[Recipe]) -> Option<ItemId> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        Open,
+        Closed,
+    }
+
+    fn visit(
+        item: ItemId,
+        recipes: &[Recipe],
+        visits: &mut BTreeMap<ItemId, Visit>,
+    ) -> Option<ItemId> {
+        match visits.get(&item) {
+            Some(Visit::Open) => return Some(item),
+            Some(Visit::Closed) => return None,
+            None => {}
+        }
+        visits.insert(item, Visit::Open);
+        let crafted = recipes
+            .iter()
+            .filter(|recipe| recipe.inputs.iter().any(|stack| stack.item == item))
+            .flat_map(|recipe| recipe.outputs.iter());
+        for stack in crafted {
+            if let Some(item) = visit(stack.item, recipes, visits) {
+                return Some(item);
+            }
+        }
+        visits.insert(item, Visit::Closed);
+        None
+    }
+
+    let mut visits = BTreeMap::new();
+    recipes
+        .iter()
+        .flat_map(|recipe| recipe.inputs.iter())
+        .find_map(|stack| visit(stack.item, recipes, &mut visits))
+}
+
+/// Block which crafts recipes out of the items in it's inventory, and the
+/// fluid in it's tank, into it's inventory.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Factory {
+    /// Recipe the factory crafts, where factories without one craft whatever
+    /// they first have the inputs for.
+    pub recipe: Option<RecipeId>,
+
+    /// Fraction of the power the factory needs which it gets, which slows it
+    /// down, where recipes without power don't need any, and factories which
+    /// consume power get it from their network.
+    pub power: f32,
+
+    /// Recipe being crafted, the inputs of which are already used up.
+    crafting: Option<RecipeId>,
+    progress: f32,
+}
+
+impl Factory {
+    pub fn new(recipe: Option<RecipeId>) -> Self {
+        Self {
+            recipe,
+            power: 1.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn crafting(&self) -> Option<RecipeId> {
+        self.crafting
+    }
+
+    /// Progress of the current craft, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+}
+
+/// System which crafts recipes in factories, and tints them by their progress.
+#[derive(Default)]
+pub struct FactorySystem;
+
+impl<'a> System<'a> for FactorySystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, Recipes>,
+        Write<'a, EventChannel<ItemEvent>>,
+        WriteStorage<'a, Factory>,
+        WriteStorage<'a, Inventory>,
+        WriteStorage<'a, Color>,
+        WriteStorage<'a, Consumer>,
+        WriteStorage<'a, FluidTank>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            recipes,
+            mut events,
+            mut factories,
+            mut inventories,
+            mut colors,
+            mut consumers,
+            mut tanks,
+        ): Self::SystemData,
+    ) {
+        let dt = delta.0.as_secs_f32();
+
+        for (entity, factory, inventory) in (&entities, &mut factories, &mut inventories).join() {
+            if factory.crafting.is_none() {
+                let mut tank = tanks.get_mut(entity);
+                let ready = |&(_, recipe): &(RecipeId, &Recipe)| {
+                    inventory.can_remove(&recipe.inputs)
+                        && recipe.fluids.iter().all(|&(fluid, amount)| {
+                            matches!(&tank, Some(tank) if tank.can_drain(fluid, amount))
+                        })
+                };
+                factory.crafting = match factory.recipe {
+                    Some(id) => Some((id, recipes.get(id))).filter(ready).map(|(id, _)| id),
+                    None => recipes.iter().find(ready).map(|(id, _)| id),
+                };
+                if let Some(id) = factory.crafting {
+                    let recipe = recipes.get(id);
+                    if let Some(tank) = tank.as_mut() {
+                        for &(fluid, amount) in &recipe.fluids {
+                            tank.drain(fluid, amount);
+                        }
+                    }
+                    let inputs = &recipe.inputs;
+                    inventory.remove(inputs);
+                    for &stack in inputs {
+                        events.single_write(ItemEvent {
+                            stack,
+                            from: Some(entity),
+                            to: None,
+                        });
+                    }
+                }
+            }
+
+            let recipe = factory.crafting.map(|id| recipes.get(id));
+            if let Some(consumer) = consumers.get_mut(entity) {
+                factory.power = consumer.satisfaction;
+                consumer.demand = recipe.map(|recipe| recipe.power).unwrap_or(0.0);
+            }
+            let recipe = match recipe {
+                Some(recipe) => recipe,
+                None => continue,
+            };
+            let speed = if recipe.power > 0.0 {
+                factory.power.clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+            factory.progress = (factory.progress + speed * dt / recipe.time).min(1.0);
+
+            // NOTE: full factories stall until their outputs are taken out.
+            if factory.progress >= 1.0 && inventory.insert(&recipe.outputs) {
+                for &stack in &recipe.outputs {
+                    events.single_write(ItemEvent {
+                        stack,
+                        from: None,
+                        to: Some(entity),
+                    });
+                }
+                factory.crafting = None;
+                factory.progress = 0.0;
+            }
+
+            // NOTE: colors are only changed when the difference shows, since
+            // every change updates the sprite.
+            let tint = Rgba::lerp(Rgba::white(), CRAFTING, factory.progress);
+            let shows = |color: &Color| (color.0 - tint).map(f32::abs).reduce_partial_max() > 0.01;
+            if colors.get(entity).map_or(false, shows) {
+                colors.get_mut(entity).unwrap().0 = tint;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn recipes_test() {
+        let items = Items::default();
+        let fluids = Fluids::default();
+        let recipes = Recipes::default();
+        let gear = recipes.get(recipes.find("gear").unwrap());
+        let plate = items.find("iron-plate").unwrap();
+        assert_eq!(gear.inputs, vec![Stack::new(plate, 2)]);
+
+        let unknown = r#"[{ "name": "a", "inputs": {}, "outputs": { "b": 1 }, "time": 1 }]"#;
+        assert!(Recipes::parse(unknown, &items, &fluids).is_err());
+
+        let cycle = r#"[
+            { "name": "a", "inputs": { "gear": 1 }, "outputs": { "stone": 1 }, "time": 1 },
+            { "name": "b", "inputs": { "stone": 1 }, "outputs": { "iron-plate": 1 }, "time": 1 },
+            { "name": "c", "inputs": { "iron-plate": 2 }, "outputs": { "gear": 1 }, "time": 1 }
+        ]"#;
+        assert!(Recipes::parse(cycle, &items, &fluids).is_err());
+    }
+
+    #[test]
+    fn factory_test() {
+        let mut world = World::new();
+        let mut system = FactorySystem;
+        System::setup(&mut system, &mut world);
+        world.insert(DeltaTime(Duration::from_millis(100)));
+
+        let items = Items::default();
+        let plate = items.find("iron-plate").unwrap();
+        let gear = items.find("gear").unwrap();
+        let inventory = Inventory::new(10).with_limit(gear, 1);
+        let factory = world
+            .create_entity()
+            .with(Factory::new(None))
+            .with(inventory)
+            .with(Color(Rgba::white()))
+            .build();
+
+        let insert = |world: &World, stacks: &[Stack]| {
+            let mut inventories = world.write_storage::<Inventory>();
+            assert!(inventories.get_mut(factory).unwrap().insert(stacks));
+        };
+        let count = |world: &World, item| {
+            world
+                .read_storage::<Inventory>()
+                .get(factory)
+                .unwrap()
+                .count(item)
+        };
+
+        // NOTE: idle factories leave their color alone.
+        let mut reader = world.write_storage::<Color>().register_reader();
+        system.run_now(&world);
+        assert_eq!(
+            world
+                .read_storage::<Color>()
+                .channel()
+                .read(&mut reader)
+                .count(),
+            0
+        );
+
+        insert(&world, &[Stack::new(plate, 5)]);
+        for _ in 0..5 {
+            system.run_now(&world);
+        }
+        assert_eq!(count(&world, plate), 3);
+        assert_eq!(count(&world, gear), 0);
+        let color = world.read_storage::<Color>().get(factory).unwrap().0;
+        assert!(color != Rgba::white());
+
+        // NOTE: the second gear doesn't fit, so the factory stalls.
+        for _ in 0..20 {
+            system.run_now(&world);
+        }
+        assert_eq!(count(&world, plate), 1);
+        assert_eq!(count(&world, gear), 1);
+        let factories = world.read_storage::<Factory>();
+        assert_eq!(factories.get(factory).unwrap().progress(), 1.0);
+    }
+}
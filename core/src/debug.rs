@@ -0,0 +1,353 @@
+//! In-game debug overlay, which shows frame times, entity counts and what's
+//! under the cursor, and outlines chunks and the hovered tile.
+
+use crate::clock::FrameRate;
+use crate::components::*;
+use crate::construction::ConstructionSite;
+use crate::crafting::{Factory, Recipes};
+use crate::fluids::{FluidTank, Fluids};
+use crate::game::Scene;
+use crate::health::Health;
+use crate::items::{Inventory, Items};
+use crate::map::{chunk_coordinate, Cell, Map, CHUNK_SIZE};
+use crate::power::PowerGrid;
+use crate::profiler::Profiler;
+use crate::render::{DebugDraw, Hud, Space};
+use crate::state::DeltaTime;
+
+use instant::Duration;
+use specs::prelude::*;
+use std::collections::VecDeque;
+use vek::*;
+
+/// Number of frames the frame time graph covers.
+const HISTORY: usize = 120;
+
+/// Number of the slowest profiled spans which are shown.
+const PROFILED: usize = 8;
+
+/// Height of a line of text, in pixels.
+const LINE: f32 = 16.0;
+
+/// Resource which holds the state of the debug overlay.
+#[derive(Default)]
+pub struct DebugHud {
+    pub visible: bool,
+
+    /// Durations of the latest frames, oldest first.
+    frame_times: VecDeque<Duration>,
+}
+
+impl DebugHud {
+    fn record(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// Average and longest frame time, in milliseconds.
+    fn frame_stats(&self) -> (f32, f32) {
+        let milliseconds = |duration: &Duration| duration.as_secs_f32() * 1000.0;
+        let total: f32 = self.frame_times.iter().map(milliseconds).sum();
+        let max = self
+            .frame_times
+            .iter()
+            .map(milliseconds)
+            .fold(0.0, f32::max);
+        (total / self.frame_times.len().max(1) as f32, max)
+    }
+}
+
+fn count<C: Component>(storage: &ReadStorage<C>) -> usize {
+    storage.join().count()
+}
+
+/// System which fills the overlay with debug information while it's visible.
+#[derive(Default)]
+pub struct DebugHudSystem;
+
+impl<'a> System<'a> for DebugHudSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, FrameRate>,
+        Read<'a, Profiler>,
+        Read<'a, Scene>,
+        Read<'a, Map<Cell>>,
+        Read<'a, Items>,
+        Read<'a, Recipes>,
+        Read<'a, PowerGrid>,
+        Read<'a, Fluids>,
+        Write<'a, DebugHud>,
+        Write<'a, Hud>,
+        Write<'a, DebugDraw>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Tile>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Color>,
+        ReadStorage<'a, TextureIndex>,
+        ReadStorage<'a, Text>,
+        ReadStorage<'a, Inventory>,
+        ReadStorage<'a, Factory>,
+        ReadStorage<'a, FluidTank>,
+        ReadStorage<'a, ConstructionSite>,
+        ReadStorage<'a, Health>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            rate,
+            profiler,
+            scene,
+            map,
+            items,
+            recipes,
+            grid,
+            fluids,
+            mut debug,
+            mut hud,
+            mut draw,
+            names,
+            tiles,
+            coordinates,
+            positions,
+            colors,
+            textures,
+            texts,
+            inventories,
+            factories,
+            tanks,
+            sites,
+            healths,
+        ): Self::SystemData,
+    ) {
+        debug.record(delta.0);
+        if !debug.visible {
+            return;
+        }
+
+        let (average, max) = debug.frame_stats();
+        let mut lines = vec![
+            format!(
+                "fps {:.0} ({:.1} ms, max {:.1} ms)",
+                1000.0 / average.max(0.001),
+                average,
+                max
+            ),
+            format!(
+                "event loop {:.0} ticks/s, {:.0} frames/s",
+                rate.ticks, rate.frames
+            ),
+            format!("entities {}", (&entities).join().count()),
+            format!(
+                "  tiles {}, coordinates {}, positions {}",
+                count(&tiles),
+                count(&coordinates),
+                count(&positions)
+            ),
+            format!(
+                "  colors {}, textures {}, names {}, texts {}",
+                count(&colors),
+                count(&textures),
+                count(&names),
+                count(&texts)
+            ),
+        ];
+
+        let world = scene.world_cursor();
+        let tile = scene.coordinate_cursor();
+        lines.push(format!(
+            "cursor ({:.2}, {:.2}), tile ({}, {})",
+            world.x, world.y, tile.x, tile.y
+        ));
+        if let Some(cell) = map.get(tile) {
+            lines.push(match cell.ore {
+                Some(ore) => format!("floor {:?}, ore {:?}", cell.floor, ore),
+                None => format!("floor {:?}", cell.floor),
+            });
+        }
+        let cell = map.get(tile).map(|cell| cell.tile);
+        lines.push(match cell {
+            None => "cell outside of the map".into(),
+            Some(None) => "cell empty".into(),
+            Some(Some(entity)) => {
+                let name = names.get(entity).map(|name| name.0.as_str());
+                format!("cell {:?} {}", entity.id(), name.unwrap_or("unnamed"))
+            }
+        });
+        if let Some(health) = cell.flatten().and_then(|entity| healths.get(entity)) {
+            lines.push(format!(
+                "  health {:.0}/{:.0}",
+                health.current(),
+                health.max()
+            ));
+        }
+        if let Some(site) = cell.flatten().and_then(|entity| sites.get(entity)) {
+            let used: Vec<_> = site
+                .used()
+                .iter()
+                .map(|stack| format!("{} {}", items.get(stack.item).name, stack.amount))
+                .collect();
+            lines.push(format!(
+                "  building {:.0}%, used {}",
+                site.progress() * 100.0,
+                if used.is_empty() {
+                    "nothing".into()
+                } else {
+                    used.join(", ")
+                }
+            ));
+        }
+        if let Some(inventory) = cell.flatten().and_then(|entity| inventories.get(entity)) {
+            let stacks: Vec<_> = inventory
+                .iter()
+                .map(|stack| format!("{} {}", items.get(stack.item).name, stack.amount))
+                .collect();
+            lines.push(if inventory.is_empty() {
+                "  inventory empty".into()
+            } else {
+                format!("  inventory {}", stacks.join(", "))
+            });
+        }
+        if let Some(factory) = cell.flatten().and_then(|entity| factories.get(entity)) {
+            lines.push(match factory.crafting() {
+                Some(recipe) => format!(
+                    "  crafting {} {:.0}%",
+                    recipes.get(recipe).name,
+                    factory.progress() * 100.0
+                ),
+                None => "  crafting nothing".into(),
+            });
+        }
+        if let Some(tank) = cell.flatten().and_then(|entity| tanks.get(entity)) {
+            let fluid = tank.fluid().map(|fluid| fluids.get(fluid).name.as_str());
+            lines.push(format!(
+                "  fluid {} {:.1}/{:.0}",
+                fluid.unwrap_or("none"),
+                tank.amount(),
+                tank.capacity()
+            ));
+        }
+        let network = cell.flatten().and_then(|entity| grid.network(entity));
+        if let Some((network, stats)) = network.and_then(|id| Some((id, grid.get(id)?))) {
+            lines.push(format!(
+                "  power network {}, supply {:.1}, demand {:.1}, stored {:.0}/{:.0}",
+                network, stats.supply, stats.demand, stats.charge, stats.capacity
+            ));
+        }
+
+        let capturing = if profiler.is_capturing() {
+            ", capturing trace (F9)"
+        } else {
+            ""
+        };
+        lines.push(format!("profile, average / p95 ms{}", capturing));
+        let milliseconds = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        for (name, stats) in profiler.all_stats().into_iter().take(PROFILED) {
+            lines.push(format!(
+                "  {:<18} {:6.2} / {:6.2}",
+                name,
+                milliseconds(stats.average),
+                milliseconds(stats.p95)
+            ));
+        }
+
+        // NOTE: tiles are centered on their coordinate, so borders are offset by half.
+        let bounds = scene.visible_bounds();
+        let chunks = chunk_coordinate(bounds.min.floor().numcast().unwrap())
+            ..=chunk_coordinate(bounds.max.ceil().numcast().unwrap());
+        let border = Rgba::new(0.2, 0.4, 1.0, 0.8);
+        for x in chunks.start().x..=chunks.end().x + 1 {
+            let x = (x * CHUNK_SIZE) as f32 - 0.5;
+            let (a, b) = (Vec2::new(x, bounds.min.y), Vec2::new(x, bounds.max.y));
+            draw.line(Space::World, a, b, border, 0.0);
+        }
+        for y in chunks.start().y..=chunks.end().y + 1 {
+            let y = (y * CHUNK_SIZE) as f32 - 0.5;
+            let (a, b) = (Vec2::new(bounds.min.x, y), Vec2::new(bounds.max.x, y));
+            draw.line(Space::World, a, b, border, 0.0);
+        }
+
+        let tile_bounds = Aabr {
+            min: tile.numcast().unwrap() - 0.5,
+            max: tile.numcast().unwrap() + 0.5,
+        };
+        draw.rect(
+            Space::World,
+            tile_bounds,
+            Rgba::new(1.0, 0.9, 0.2, 1.0),
+            0.0,
+        );
+
+        let origin = Vec2::broadcast(8.0);
+        let graph_height = 50.0;
+        let width = 360.0;
+        let height = lines.len() as f32 * LINE + graph_height + 8.0;
+        hud.rect(
+            Aabr {
+                min: origin - 4.0,
+                max: origin + Vec2::new(width, height) + 4.0,
+            },
+            Rgba::new(0.0, 0.0, 0.0, 0.6),
+        );
+
+        for (i, line) in lines.into_iter().enumerate() {
+            let position = origin + Vec2::new(0.0, i as f32 * LINE);
+            hud.text(position, Text::new(line, LINE), Rgba::white());
+        }
+
+        // NOTE: bars are scaled so that a 60 fps frame is half the graph.
+        let baseline = origin.y + height;
+        let scale = graph_height / 33.3;
+        let bar = width / HISTORY as f32;
+        for (i, frame_time) in debug.frame_times.iter().enumerate() {
+            let milliseconds = frame_time.as_secs_f32() * 1000.0;
+            let color = match milliseconds {
+                x if x <= 17.0 => Rgba::new(0.2, 0.9, 0.2, 1.0),
+                x if x <= 34.0 => Rgba::new(0.9, 0.8, 0.2, 1.0),
+                _ => Rgba::new(0.9, 0.2, 0.2, 1.0),
+            };
+            let x = origin.x + i as f32 * bar;
+            let top = baseline - (milliseconds * scale).min(graph_height);
+            hud.rect(
+                Aabr {
+                    min: Vec2::new(x, top),
+                    max: Vec2::new(x + bar.max(1.0), baseline),
+                },
+                color,
+            );
+        }
+
+        let target = baseline - 16.7 * scale;
+        hud.rect(
+            Aabr {
+                min: Vec2::new(origin.x, target),
+                max: Vec2::new(origin.x + width, target + 1.0),
+            },
+            Rgba::new(1.0, 1.0, 1.0, 0.5),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_history_test() {
+        let mut debug = DebugHud::default();
+        for i in 0..HISTORY + 10 {
+            debug.record(Duration::from_millis(if i == HISTORY { 40 } else { 10 }));
+        }
+        assert_eq!(debug.frame_times.len(), HISTORY);
+
+        let (average, max) = debug.frame_stats();
+        assert!((average - (10.0 + 30.0 / HISTORY as f32)).abs() < 0.01);
+        assert!((max - 40.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,270 @@
+//! Drills, which mine the ore under them into items, and put them out onto
+//! the belts next to them, or into their own inventory.
+
+use crate::components::*;
+use crate::conveyor::Conveyor;
+use crate::items::{Inventory, ItemEvent, Items, Stack};
+use crate::map::{neighbours, Cell, Map};
+use crate::state::DeltaTime;
+use crate::worldgen::Ore;
+
+use shrev::EventChannel;
+use specs::prelude::*;
+use specs::Component;
+use vek::*;
+
+/// Seconds a drill of the first tier takes to mine an item, from a single
+/// tile of ore.
+const MINING_TIME: f32 = 4.0;
+
+/// Drill which mines the ore under it's footprint, where the most common ore
+/// it can mine is mined, as fast as there are tiles of it.
+#[derive(Component, Clone, Debug)]
+pub struct Drill {
+    /// Hardest ore the drill can mine, which also makes it faster.
+    pub tier: u32,
+
+    /// How many times faster the drill mines, which other blocks can raise.
+    pub boost: f32,
+
+    /// Progress towards the next item, which stays full while there's no room
+    /// for the item.
+    progress: f32,
+}
+
+impl Drill {
+    pub fn new(tier: u32) -> Self {
+        Self {
+            tier,
+            boost: 1.0,
+            progress: 0.0,
+        }
+    }
+
+    /// Items mined per second from `tiles` tiles of an ore.
+    pub fn rate(&self, tiles: usize) -> f32 {
+        tiles as f32 * self.tier as f32 * self.boost / MINING_TIME
+    }
+}
+
+/// Most common ore under a footprint which a drill of `tier` mines, and how
+/// many tiles of it there are, where ties go to the harder ore.
+pub fn mined_ore(
+    map: &Map<Cell>,
+    tiles: impl Iterator<Item = Vec2<i32>>,
+    tier: u32,
+) -> Option<(Ore, usize)> {
+    let mut counts: Vec<(Ore, usize)> = Vec::new();
+    for ore in tiles.filter_map(|tile| map.get(tile).and_then(|cell| cell.ore)) {
+        if ore.hardness() > tier {
+            continue;
+        }
+        match counts.iter_mut().find(|(o, _)| *o == ore) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((ore, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(ore, count)| (count, ore.hardness()))
+}
+
+/// System which mines items with drills, and puts them out.
+#[derive(Default)]
+pub struct DrillSystem;
+
+impl<'a> System<'a> for DrillSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, Map<Cell>>,
+        Read<'a, Items>,
+        Write<'a, EventChannel<ItemEvent>>,
+        WriteStorage<'a, Drill>,
+        WriteStorage<'a, Inventory>,
+        WriteStorage<'a, Conveyor>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Footprint>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Io>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            map,
+            items,
+            mut events,
+            mut drills,
+            mut inventories,
+            mut conveyors,
+            coordinates,
+            footprints,
+            rotations,
+            ios,
+        ): Self::SystemData,
+    ) {
+        let dt = delta.0.as_secs_f32();
+
+        for (entity, drill, coordinate, footprint, rotation, io) in (
+            &entities,
+            &mut drills,
+            &coordinates,
+            footprints.maybe(),
+            rotations.maybe(),
+            ios.maybe(),
+        )
+            .join()
+        {
+            let footprint = footprint.copied().unwrap_or_default();
+            let rotation = rotation.copied().unwrap_or_default();
+            let tiles: Vec<_> = footprint.tiles(coordinate.0, rotation).collect();
+
+            // NOTE: belts which face the drill would only give the item back.
+            let outputs = io.map(|io| io.outputs(rotation)).unwrap_or(Sides::ALL);
+            let belt = neighbours(&tiles)
+                .filter(|&(_, direction)| outputs.contains(direction))
+                .filter_map(|(neighbour, direction)| {
+                    let belt = map.tile(neighbour)?;
+                    let conveyor = conveyors.get(belt)?;
+                    if conveyor.direction != direction.opposite() && conveyor.has_room() {
+                        Some(belt)
+                    } else {
+                        None
+                    }
+                })
+                .next();
+
+            // NOTE: a single item goes out per frame, which is as many as a
+            // belt takes anyways, and what the drill holds goes out first.
+            let held = inventories
+                .get(entity)
+                .and_then(|inventory| inventory.iter().next());
+            if let (Some(belt), Some(held)) = (belt, held) {
+                let stack = Stack::new(held.item, 1);
+                inventories.get_mut(entity).unwrap().remove(&[stack]);
+                conveyors.get_mut(belt).unwrap().push(stack.item);
+                events.single_write(ItemEvent {
+                    stack,
+                    from: Some(entity),
+                    to: Some(belt),
+                });
+                continue;
+            }
+
+            let (ore, count) = match mined_ore(&map, tiles.iter().copied(), drill.tier) {
+                Some(ore) => ore,
+                None => continue,
+            };
+            let item = match items.find(ore.item()) {
+                Some(item) => item,
+                None => continue,
+            };
+
+            drill.progress = (drill.progress + drill.rate(count) * dt).min(1.0);
+            if drill.progress < 1.0 {
+                continue;
+            }
+
+            let stack = Stack::new(item, 1);
+            let to = match belt {
+                Some(belt) => {
+                    conveyors.get_mut(belt).unwrap().push(item);
+                    Some(belt)
+                }
+                None => inventories
+                    .get_mut(entity)
+                    .map(|inventory| inventory.insert(&[stack]))
+                    .filter(|&inserted| inserted)
+                    .map(|_| entity),
+            };
+            if to.is_some() {
+                drill.progress = 0.0;
+                events.single_write(ItemEvent {
+                    stack,
+                    from: None,
+                    to,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conveyor::spawn_conveyor;
+    use instant::Duration;
+
+    fn world() -> (World, DrillSystem) {
+        let mut world = World::new();
+        let mut system = DrillSystem;
+        System::setup(&mut system, &mut world);
+        world.register::<Tile>();
+        world.insert(DeltaTime(Duration::from_millis(100)));
+
+        let mut map = world.write_resource::<Map<Cell>>();
+        for (x, y, ore) in [(2, 2, Ore::Iron), (3, 2, Ore::Copper), (2, 3, Ore::Copper)].iter() {
+            map.get_mut(Vec2::new(*x, *y)).unwrap().ore = Some(*ore);
+        }
+        drop(map);
+        (world, system)
+    }
+
+    fn drill(world: &mut World, tier: u32) -> Entity {
+        world
+            .create_entity()
+            .with(Coordinate(Vec2::new(2, 2)))
+            .with(Footprint(Vec2::new(2, 2)))
+            .with(Drill::new(tier))
+            .with(Inventory::new(10))
+            .build()
+    }
+
+    fn count(world: &World, entity: Entity, name: &str) -> u32 {
+        let item = world.read_resource::<Items>().find(name).unwrap();
+        let inventories = world.read_storage::<Inventory>();
+        inventories.get(entity).unwrap().count(item)
+    }
+
+    #[test]
+    fn mined_ore_test() {
+        let (world, _) = world();
+        let map = world.read_resource::<Map<Cell>>();
+        let tiles = || Footprint(Vec2::new(2, 2)).tiles(Vec2::new(2, 2), Rotation::Deg0);
+
+        assert_eq!(mined_ore(&map, tiles(), 1), Some((Ore::Copper, 2)));
+        assert_eq!(mined_ore(&map, tiles(), 2), Some((Ore::Copper, 2)));
+        let iron = Footprint(Vec2::one()).tiles(Vec2::new(2, 2), Rotation::Deg0);
+        assert_eq!(mined_ore(&map, iron, 1), None);
+    }
+
+    #[test]
+    fn drill_test() {
+        let (mut world, mut system) = world();
+        let slow = drill(&mut world, 1);
+
+        // NOTE: two tiles of copper at tier one take two seconds per item.
+        for _ in 0..40 {
+            system.run_now(&world);
+        }
+        assert_eq!(count(&world, slow, "copper-ore"), 2);
+
+        let mut drills = world.write_storage::<Drill>();
+        drills.get_mut(slow).unwrap().boost = 2.0;
+        drop(drills);
+        for _ in 0..20 {
+            system.run_now(&world);
+        }
+        assert_eq!(count(&world, slow, "copper-ore"), 4);
+
+        // NOTE: belts next to the drill take what it holds first.
+        let belt = spawn_conveyor(&mut world, Vec2::new(4, 2), Conveyor::new(Direction::Right));
+        system.run_now(&world);
+        assert_eq!(count(&world, slow, "copper-ore"), 3);
+        let conveyors = world.read_storage::<Conveyor>();
+        assert_eq!(conveyors.get(belt).unwrap().items().count(), 1);
+    }
+}
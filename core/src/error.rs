@@ -3,6 +3,15 @@ use crate::render::RenderError;
 #[derive(Clone, Debug)]
 pub enum Error {
     RenderError(RenderError),
+
+    /// Failed to create an OpenGL context.
+    ContextError(String),
+
+    /// Invalid settings file or command line argument.
+    SettingsError(String),
+
+    /// Invalid game data, like item definitions.
+    DataError(String),
 }
 
 impl From<RenderError> for Error {
@@ -0,0 +1,435 @@
+//! Fluids, which are defined as data, and the tanks which hold them, where
+//! fluids flow between touching tanks and pipes, from fuller to emptier ones.
+
+use crate::components::*;
+use crate::map::{neighbours, Cell, Map};
+use crate::state::DeltaTime;
+use crate::Error;
+
+use serde::Deserialize;
+use specs::prelude::*;
+use specs::Component;
+use std::collections::HashMap;
+use vek::*;
+
+/// Fluid definitions, which are bundled with the game.
+static FLUIDS: &str = include_str!("../assets/fluids.json");
+
+/// Seconds between fluid ticks, which don't depend on the frame rate, so
+/// fluids flow the same way on every machine.
+pub const TICK: f32 = 1.0 / 30.0;
+
+/// Most ticks run in a single frame, after which the fluids fall behind.
+const MAX_TICKS: u32 = 8;
+
+/// Fraction of the difference between two tanks which flows every tick.
+const FLOW: f32 = 0.5;
+
+/// Amount below which tanks are considered empty, and forget their fluid.
+const EMPTY: f32 = 0.001;
+
+/// Tint of empty tanks, which is blended towards the color of their fluid.
+const EMPTY_TINT: Rgba<f32> = Rgba {
+    r: 0.6,
+    g: 0.6,
+    b: 0.6,
+    a: 1.0,
+};
+
+/// Index of a fluid definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FluidId(pub u16);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FluidDef {
+    pub name: String,
+
+    /// Color of the fluid, which tints the tanks which hold it.
+    pub color: [f32; 4],
+}
+
+impl FluidDef {
+    pub fn color(&self) -> Rgba<f32> {
+        Rgba::from(self.color)
+    }
+}
+
+/// Resource of every fluid definition.
+#[derive(Clone, Debug)]
+pub struct Fluids {
+    defs: Vec<FluidDef>,
+}
+
+impl Default for Fluids {
+    fn default() -> Self {
+        Self::parse(FLUIDS).expect("Bundled fluids are valid.")
+    }
+}
+
+impl Fluids {
+    /// Parses a JSON list of fluid definitions, where names must be unique.
+    pub fn parse(json: &str) -> Result<Self, Error> {
+        let error = |message| Error::DataError(format!("fluids: {}", message));
+        let defs: Vec<FluidDef> =
+            serde_json::from_str(json).map_err(|err| error(err.to_string()))?;
+
+        for (i, def) in defs.iter().enumerate() {
+            if def.name.is_empty() {
+                return Err(error(format!("fluid {} has no name", i)));
+            }
+            if defs[..i].iter().any(|other| other.name == def.name) {
+                return Err(error(format!("fluid `{}` is defined twice", def.name)));
+            }
+        }
+        if defs.len() > u16::MAX as usize {
+            return Err(error("too many fluids".into()));
+        }
+        Ok(Self { defs })
+    }
+
+    pub fn find(&self, name: &str) -> Option<FluidId> {
+        self.defs
+            .iter()
+            .position(|def| def.name == name)
+            .map(|i| FluidId(i as u16))
+    }
+
+    pub fn get(&self, fluid: FluidId) -> &FluidDef {
+        &self.defs[fluid.0 as usize]
+    }
+}
+
+/// Fluid held by a block, where pipes are small tanks, and a tank only holds
+/// a single fluid at a time.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct FluidTank {
+    fluid: Option<FluidId>,
+    amount: f32,
+    capacity: f32,
+
+    /// Whether the block is tinted by how full it is.
+    tinted: bool,
+}
+
+impl FluidTank {
+    pub fn new(capacity: f32) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn tinted(mut self) -> Self {
+        self.tinted = true;
+        self
+    }
+
+    pub fn fluid(&self) -> Option<FluidId> {
+        self.fluid
+    }
+
+    pub fn amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn capacity(&self) -> f32 {
+        self.capacity
+    }
+
+    /// How full the tank is, from 0 to 1.
+    pub fn level(&self) -> f32 {
+        if self.capacity > 0.0 {
+            self.amount / self.capacity
+        } else {
+            0.0
+        }
+    }
+
+    pub fn accepts(&self, fluid: FluidId) -> bool {
+        self.fluid.is_none() || self.fluid == Some(fluid)
+    }
+
+    /// Fills the tank with as much of a fluid as fits, which is returned.
+    pub fn fill(&mut self, fluid: FluidId, amount: f32) -> f32 {
+        if !self.accepts(fluid) {
+            return 0.0;
+        }
+        let filled = amount.min(self.capacity - self.amount).max(0.0);
+        if filled > 0.0 {
+            self.fluid = Some(fluid);
+            self.amount += filled;
+        }
+        filled
+    }
+
+    pub fn can_drain(&self, fluid: FluidId, amount: f32) -> bool {
+        self.fluid == Some(fluid) && self.amount >= amount
+    }
+
+    /// Drains an amount of a fluid, or nothing if there isn't enough of it.
+    pub fn drain(&mut self, fluid: FluidId, amount: f32) -> bool {
+        if !self.can_drain(fluid, amount) {
+            return false;
+        }
+        self.amount -= amount;
+        if self.amount < EMPTY {
+            self.fluid = None;
+            self.amount = 0.0;
+        }
+        true
+    }
+}
+
+/// Block which fills it's own tank out of nothing.
+#[derive(Component, Clone, Debug)]
+pub struct Pump {
+    pub fluid: FluidId,
+
+    /// Amount pumped every second.
+    pub rate: f32,
+}
+
+/// System which pumps fluids, and lets them flow between touching tanks, at a
+/// fixed rate of ticks.
+#[derive(Default)]
+pub struct FluidSystem {
+    /// Time which wasn't simulated yet.
+    accumulator: f32,
+
+    /// Pairs of touching tanks, by increasing entity id.
+    links: Vec<(Entity, Entity)>,
+    degrees: HashMap<Entity, u32>,
+    flows: Vec<(Entity, Entity, FluidId, f32)>,
+}
+
+impl FluidSystem {
+    /// Moves fluids between every linked pair of tanks, where the flow out
+    /// of and into every tank is limited by it's number of links, so tanks
+    /// neither empty below nothing nor overflow.
+    fn step(&mut self, tanks: &mut WriteStorage<FluidTank>) {
+        let mut claimed: HashMap<Entity, FluidId> = HashMap::new();
+        self.flows.clear();
+
+        for &(a, b) in &self.links {
+            let (tank_a, tank_b) = (tanks.get(a).unwrap(), tanks.get(b).unwrap());
+            let fluid_a = tank_a.fluid.or_else(|| claimed.get(&a).copied());
+            let fluid_b = tank_b.fluid.or_else(|| claimed.get(&b).copied());
+            let fluid = match (fluid_a, fluid_b) {
+                (Some(x), Some(y)) if x != y => continue,
+                (Some(fluid), _) | (_, Some(fluid)) => fluid,
+                (None, None) => continue,
+            };
+
+            // NOTE: the amount which would even out how full both tanks are.
+            let (ca, cb) = (tank_a.capacity, tank_b.capacity);
+            if ca + cb <= 0.0 {
+                continue;
+            }
+            let even = (tank_a.amount * cb - tank_b.amount * ca) / (ca + cb);
+            let (from, to, source, target) = if even >= 0.0 {
+                (a, b, tank_a, tank_b)
+            } else {
+                (b, a, tank_b, tank_a)
+            };
+            let amount = (even.abs() * FLOW)
+                .min(source.amount / self.degrees[&from] as f32)
+                .min((target.capacity - target.amount) / self.degrees[&to] as f32);
+            if amount > 0.0 {
+                claimed.insert(to, fluid);
+                self.flows.push((from, to, fluid, amount));
+            }
+        }
+
+        // NOTE: empty tanks are claimed by the first fluid which flows into
+        // them, so fluids which flow into the same tank never differ.
+        for &(from, to, fluid, amount) in &self.flows {
+            if tanks.get(to).unwrap().accepts(fluid)
+                && tanks.get_mut(from).unwrap().drain(fluid, amount)
+            {
+                tanks.get_mut(to).unwrap().fill(fluid, amount);
+            }
+        }
+    }
+}
+
+impl<'a> System<'a> for FluidSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, Map<Cell>>,
+        Read<'a, Fluids>,
+        WriteStorage<'a, FluidTank>,
+        WriteStorage<'a, Color>,
+        ReadStorage<'a, Pump>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Footprint>,
+        ReadStorage<'a, Rotation>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            map,
+            fluids,
+            mut tanks,
+            mut colors,
+            pumps,
+            coordinates,
+            footprints,
+            rotations,
+        ): Self::SystemData,
+    ) {
+        // NOTE: links are found once a frame, since blocks are only placed
+        // and removed between frames.
+        self.links.clear();
+        self.degrees.clear();
+        for (entity, coordinate, footprint, rotation, _) in (
+            &entities,
+            &coordinates,
+            footprints.maybe(),
+            rotations.maybe(),
+            &tanks,
+        )
+            .join()
+        {
+            let footprint = footprint.copied().unwrap_or_default();
+            let rotation = rotation.copied().unwrap_or_default();
+            let tiles: Vec<_> = footprint.tiles(coordinate.0, rotation).collect();
+            let mut links: Vec<_> = neighbours(&tiles)
+                .filter_map(|(neighbour, _)| map.tile(neighbour))
+                .filter(|&other| other.id() > entity.id() && tanks.contains(other))
+                .collect();
+            links.sort_unstable();
+            links.dedup();
+            for other in links {
+                *self.degrees.entry(entity).or_insert(0) += 1;
+                *self.degrees.entry(other).or_insert(0) += 1;
+                self.links.push((entity, other));
+            }
+        }
+
+        self.accumulator += delta.0.as_secs_f32();
+        let mut ticks = 0;
+        while self.accumulator >= TICK && ticks < MAX_TICKS {
+            for (tank, pump) in (&mut tanks, &pumps).join() {
+                tank.fill(pump.fluid, pump.rate * TICK);
+            }
+            self.step(&mut tanks);
+            self.accumulator -= TICK;
+            ticks += 1;
+        }
+        self.accumulator = self.accumulator.min(TICK);
+
+        for (entity, tank) in (&entities, &tanks).join() {
+            if !tank.tinted {
+                continue;
+            }
+            let tint = match tank.fluid {
+                Some(fluid) => Rgba::lerp(EMPTY_TINT, fluids.get(fluid).color(), tank.level()),
+                None => EMPTY_TINT,
+            };
+            // NOTE: colors are only changed when the difference shows, since
+            // every change updates the sprite, and even joining them mutably
+            // counts as a change.
+            let shows = |color: &Color| (color.0 - tint).map(f32::abs).reduce_partial_max() > 0.01;
+            if colors.get(entity).map_or(false, shows) {
+                colors.get_mut(entity).unwrap().0 = tint;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    fn tank(world: &mut World, x: i32, tank: FluidTank) -> Entity {
+        let entity = world
+            .create_entity()
+            .with(Coordinate(Vec2::new(x, 1)))
+            .with(tank)
+            .build();
+        let mut map = world.write_resource::<Map<Cell>>();
+        map.get_mut(Vec2::new(x, 1)).unwrap().tile = Some(entity);
+        entity
+    }
+
+    #[test]
+    fn flow_test() {
+        let mut world = World::new();
+        let mut system = FluidSystem::default();
+        System::setup(&mut system, &mut world);
+        world.insert(DeltaTime(Duration::from_secs(1)));
+
+        let fluids = Fluids::default();
+        let water = fluids.find("water").unwrap();
+        let oil = fluids.find("oil").unwrap();
+
+        let mut other = FluidTank::new(10.0);
+        other.fill(oil, 5.0);
+        let oily = tank(&mut world, 0, other);
+        let mut full = FluidTank::new(100.0);
+        full.fill(water, 100.0);
+        let left = tank(&mut world, 1, full);
+        let pipe = tank(&mut world, 2, FluidTank::new(10.0));
+        let right = tank(&mut world, 3, FluidTank::new(100.0));
+
+        for _ in 0..30 {
+            system.run_now(&world);
+        }
+
+        // NOTE: fluids even out without being lost, and don't mix.
+        let tanks = world.read_storage::<FluidTank>();
+        let amount = |entity| tanks.get(entity).unwrap().amount();
+        let total = amount(left) + amount(pipe) + amount(right);
+        assert!((total - 100.0).abs() < 0.01);
+        for &entity in &[pipe, right] {
+            let level = tanks.get(entity).unwrap().level();
+            assert!((level - 100.0 / 210.0).abs() < 0.01, "{}", level);
+            assert_eq!(tanks.get(entity).unwrap().fluid(), Some(water));
+        }
+        assert_eq!(amount(oily), 5.0);
+    }
+
+    #[test]
+    fn tick_test() {
+        let mut world = World::new();
+        let mut system = FluidSystem::default();
+        System::setup(&mut system, &mut world);
+        let water = Fluids::default().find("water").unwrap();
+        let pump = tank(&mut world, 1, FluidTank::new(100.0));
+        world
+            .write_storage()
+            .insert(
+                pump,
+                Pump {
+                    fluid: water,
+                    rate: 3.0,
+                },
+            )
+            .unwrap();
+
+        // NOTE: the same time pumps the same amount, whatever the frame rate.
+        for &frames in &[1, 10] {
+            world
+                .write_storage::<FluidTank>()
+                .insert(pump, FluidTank::new(100.0))
+                .unwrap();
+            system.accumulator = 0.0;
+            world.insert(DeltaTime(Duration::from_secs_f32(0.25 / frames as f32)));
+            for _ in 0..frames {
+                system.run_now(&world);
+            }
+            let amount = world
+                .read_storage::<FluidTank>()
+                .get(pump)
+                .unwrap()
+                .amount();
+            assert!((amount - 0.7).abs() < 0.01, "{}", amount);
+        }
+    }
+}
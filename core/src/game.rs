@@ -1,12 +1,21 @@
 //! Crate which holds the main event loop and various generic game
 //! resources used globally throughout the game.
 
+use crate::builder::BuildChoice;
+use crate::clock::{Clock, FrameRate};
+use crate::construction;
+use crate::debug::DebugHud;
+use crate::profiler::Profiler;
+#[cfg(feature = "nat")]
+use crate::render::Capture;
+use crate::settings::Settings;
 use crate::window::Window;
+use crate::worldgen::WorldGen;
 use crate::*;
-use rand::prelude::*;
+use instant::Instant;
 use specs::prelude::*;
 use vek::*;
-use winit::dpi::PhysicalPosition;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 #[derive(Default)]
 struct KeyState {
@@ -14,6 +23,15 @@ struct KeyState {
     down: bool,
     left: bool,
     right: bool,
+
+    /// Toggle keys, which only act when they're first pressed.
+    debug: bool,
+    fullscreen: bool,
+    reload: bool,
+    trace: bool,
+    screenshot: bool,
+    record: bool,
+    rotate: bool,
 }
 
 impl From<KeyState> for Vec2<f32> {
@@ -38,24 +56,78 @@ pub mod resources {
     #[derive(Default)]
     pub struct Frame(pub u64);
 
+    /// Mouse input of the current frame, which the user interface sees first,
+    /// and marks as `captured` when it's used, so the world ignores it.
     #[derive(Default)]
+    pub struct Input {
+        /// Whether the left button is held.
+        pub pressed: bool,
+
+        /// Whether the left button was pressed since the last frame.
+        pub clicked: bool,
+
+        /// Whether the right button was pressed since the last frame.
+        pub right_clicked: bool,
+
+        /// Lines scrolled since the last frame, positive when scrolling up.
+        pub scroll: f32,
+
+        pub captured: bool,
+    }
+
+    /// Size of the screen and position of the cursor, in physical pixels,
+    /// which are only updated when the window is resized or the cursor moves.
     pub struct Scene {
         screen_size: Vec2<i32>,
         cursor_state: Vec2<i32>,
+
+        /// Physical pixels per logical pixel, which screen-space is measured in,
+        /// so that interfaces keep their size on high DPI screens.
+        scale_factor: f32,
+    }
+
+    impl Default for Scene {
+        fn default() -> Self {
+            Scene::new(Vec2::zero(), Vec2::zero(), 1.0)
+        }
     }
 
     impl Scene {
-        pub fn new(screen_size: Vec2<i32>, cursor_state: Vec2<i32>) -> Self {
+        pub fn new(screen_size: Vec2<i32>, cursor_state: Vec2<i32>, scale_factor: f32) -> Self {
             Scene {
                 screen_size,
                 cursor_state,
+                scale_factor,
             }
         }
 
+        pub fn resize(&mut self, screen_size: Vec2<i32>, scale_factor: f32) {
+            self.screen_size = screen_size;
+            self.scale_factor = scale_factor;
+        }
+
+        pub fn move_cursor(&mut self, cursor_state: Vec2<i32>) {
+            self.cursor_state = cursor_state;
+        }
+
         pub fn screen_dimentions(&self) -> &Vec2<i32> {
             &self.screen_size
         }
 
+        pub fn scale_factor(&self) -> f32 {
+            self.scale_factor
+        }
+
+        /// size of the screen in logical pixels
+        pub fn logical_dimensions(&self) -> Vec2<f32> {
+            Self::convert(&self.screen_size) / self.scale_factor
+        }
+
+        /// cursor position in logical pixels, from the top left of the screen
+        pub fn logical_cursor(&self) -> Vec2<f32> {
+            Self::convert(&self.cursor_state) / self.scale_factor
+        }
+
         /// normalize cursor coordinates into clip-space (-1 to 1)
         fn normalize(screen_size: Vec2<f32>, cursor_position: Vec2<f32>) -> Vec2<f32> {
             let mut v = ((cursor_position / screen_size) - Vec2::new(0.5, 0.5)) * 2.0;
@@ -79,6 +151,7 @@ pub mod resources {
             let Self {
                 screen_size,
                 cursor_state,
+                ..
             } = self;
 
             let fscreen = Self::convert(screen_size);
@@ -95,6 +168,35 @@ pub mod resources {
             self.world_cursor().round().numcast().unwrap()
         }
 
+        /// world-space rectangle which is visible on screen, computed by taking
+        /// the corners of clip-space back into world-space.
+        pub fn visible_bounds(&self) -> Aabr<f32> {
+            let imatrix = self.transform().inverted();
+            let corner = |x, y| {
+                let [x, y, _, _] = (imatrix * Vec4::new(x, y, 0.0, 1.0)).into_array();
+                Vec2::new(x, y)
+            };
+
+            let mut bounds = Aabr::new_empty(corner(-1.0, -1.0));
+            bounds.expand_to_contain_point(corner(1.0, -1.0));
+            bounds.expand_to_contain_point(corner(-1.0, 1.0));
+            bounds.expand_to_contain_point(corner(1.0, 1.0));
+            bounds
+        }
+
+        /// screen-space to clip-space transformation, where positions are in
+        /// logical pixels starting at the top left of the screen.
+        pub fn screen_transform(&self) -> Mat4<f32> {
+            let screen_size = self.logical_dimensions();
+            #[rustfmt::skip]
+            let frustum = FrustumPlanes::<f32> {
+                left: 0.0, right: screen_size.x,
+                bottom: screen_size.y, top: 0.0,
+                near: -10., far: 10.,
+            };
+            Mat4::orthographic_rh_zo(frustum)
+        }
+
         /// main world to clip-space transformation
         pub fn transform(&self) -> Mat4<f32> {
             let Self { screen_size, .. } = self;
@@ -118,32 +220,70 @@ pub mod resources {
     }
 }
 
-pub fn play() {
-    let event_loop = winit::event_loop::EventLoop::new();
-    let (window, renderer) = Window::new(&event_loop).unwrap();
-    let mut game = state::GameState::new(renderer);
+fn to_vec(size: PhysicalSize<u32>) -> Vec2<i32> {
+    Vec2::new(size.width, size.height).numcast().unwrap()
+}
+
+/// Physical pixels per logical pixel, scaled by the user interface scale.
+fn scale_factor(window: &Window, settings: &Settings) -> f32 {
+    window.device_pixel_ratio() as f32 * settings.ui_scale
+}
+
+/// Starts capturing a trace, or saves the one being captured.
+fn toggle_trace(profiler: &mut Profiler) {
+    if !profiler.is_capturing() {
+        profiler.start_capture();
+        return;
+    }
 
-    let mut rng = rand::thread_rng();
-    let mut a = vec!["a"; 100];
-    a.extend(["b"; 100].as_ref());
-    a.extend(["c"; 100].as_ref());
-    a.extend(["d"; 100].as_ref());
-    a.shuffle(&mut rng);
-    let mut content = a.iter().cycle();
+    if let Some(trace) = profiler.finish_capture() {
+        // NOTE: there is no file system on web, so the trace is logged instead.
+        #[cfg(feature = "web")]
+        logger::log(&trace);
 
-    for i in 0..5 {
-        for j in 0..5 {
-            game.create_block(i, j, content.next().unwrap(), rng.gen_range(0.5, 0.9));
+        #[cfg(feature = "nat")]
+        match profiler::save_trace(&trace) {
+            Ok(path) => logger::log(&format!("saved trace to {}", path)),
+            Err(err) => logger::log(&format!("failed to save trace: {}", err)),
         }
     }
+}
+
+pub fn play(mut settings: Settings) {
+    let event_loop = winit::event_loop::EventLoop::new();
+    let (window, renderer) = Window::new(&event_loop, &settings).unwrap();
+    let mut game = state::GameState::new(renderer);
+    *game.ecs.write_resource::<Settings>() = settings.clone();
+    game.ecs.write_resource::<PostProcessing>().passes = settings.post.clone();
+
+    // NOTE: the seed is logged, so that a random world can be generated again.
+    let seed = settings.seed.unwrap_or_else(rand::random);
+    logger::log(&format!("generating world from seed {}", seed));
+    let generator = WorldGen::new(seed);
+    worldgen::spawn(&mut game.ecs, &generator);
+    construction::spawn_core(&mut game.ecs, &generator);
 
     let mut key_state = KeyState::default();
-    let mut cursor_state = Default::default();
+    *game.ecs.write_resource::<Scene>() = Scene::new(
+        window.dimensions().into(),
+        Vec2::zero(),
+        scale_factor(&window, &settings),
+    );
     // let mut universe_position = Default::default();
+    let mut clock = Clock::new(settings.frame_cap, Instant::now());
+
+    // NOTE: on web, frames are drawn when the browser asks for them, which
+    // wakes up the event loop through this proxy.
+    #[cfg(feature = "web")]
+    let proxy = event_loop.create_proxy();
+    #[cfg(feature = "web")]
+    let mut frame_requested = false;
 
     event_loop.run(move |event, _, control_flow| {
         use winit::event_loop::ControlFlow;
-        *control_flow = ControlFlow::Poll;
+        if *control_flow == ControlFlow::Exit {
+            return;
+        }
 
         use winit::event::{Event, StartCause, WindowEvent};
         match event {
@@ -152,6 +292,24 @@ pub fn play() {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
 
+                WindowEvent::Resized(size) => {
+                    window.resize(size);
+                    game.ecs
+                        .write_resource::<Scene>()
+                        .resize(to_vec(size), scale_factor(&window, &settings));
+                }
+
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    window.resize(*new_inner_size);
+                    game.ecs.write_resource::<Scene>().resize(
+                        to_vec(*new_inner_size),
+                        scale_factor as f32 * settings.ui_scale,
+                    );
+                }
+
                 WindowEvent::KeyboardInput { input, .. } => {
                     use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
                     let KeyboardInput {
@@ -167,6 +325,83 @@ pub fn play() {
                             VirtualKeyCode::Left => key_state.left = held,
                             VirtualKeyCode::Right => key_state.right = held,
                             VirtualKeyCode::Space => {}
+
+                            VirtualKeyCode::R => {
+                                if held && !key_state.rotate {
+                                    let mut choice = game.ecs.write_resource::<BuildChoice>();
+                                    choice.rotation = choice.rotation.counter_clockwise();
+                                }
+                                key_state.rotate = held;
+                            }
+
+                            VirtualKeyCode::F3 => {
+                                if held && !key_state.debug {
+                                    let mut debug = game.ecs.write_resource::<DebugHud>();
+                                    debug.visible = !debug.visible;
+                                }
+                                key_state.debug = held;
+                            }
+
+                            #[cfg(feature = "nat")]
+                            VirtualKeyCode::F5 => {
+                                if held && !key_state.reload {
+                                    let args: Vec<String> = std::env::args().skip(1).collect();
+                                    match Settings::load(&args) {
+                                        Ok(new) => {
+                                            window.apply(&settings, &new);
+                                            clock.set_frame_cap(new.frame_cap);
+                                            settings = new;
+
+                                            let scale = scale_factor(&window, &settings);
+                                            let mut scene = game.ecs.write_resource::<Scene>();
+                                            let size = *scene.screen_dimentions();
+                                            scene.resize(size, scale);
+                                            drop(scene);
+                                            *game.ecs.write_resource::<Settings>() =
+                                                settings.clone();
+                                            game.ecs.write_resource::<PostProcessing>().passes =
+                                                settings.post.clone();
+                                        }
+                                        Err(err) => logger::log(&format!(
+                                            "failed to reload settings: {:?}",
+                                            err
+                                        )),
+                                    }
+                                }
+                                key_state.reload = held;
+                            }
+
+                            VirtualKeyCode::F9 => {
+                                if held && !key_state.trace {
+                                    toggle_trace(&mut game.ecs.write_resource::<Profiler>());
+                                }
+                                key_state.trace = held;
+                            }
+
+                            VirtualKeyCode::F11 => {
+                                if held && !key_state.fullscreen {
+                                    window.toggle_fullscreen();
+                                }
+                                key_state.fullscreen = held;
+                            }
+
+                            #[cfg(feature = "nat")]
+                            VirtualKeyCode::F12 => {
+                                if held && !key_state.screenshot {
+                                    game.ecs.write_resource::<Capture>().screenshot = true;
+                                }
+                                key_state.screenshot = held;
+                            }
+
+                            #[cfg(feature = "nat")]
+                            VirtualKeyCode::F10 => {
+                                if held && !key_state.record {
+                                    let mut capture = game.ecs.write_resource::<Capture>();
+                                    capture.recording = !capture.recording;
+                                }
+                                key_state.record = held;
+                            }
+
                             _ => {}
                         };
                     };
@@ -174,7 +409,31 @@ pub fn play() {
 
                 WindowEvent::CursorMoved { position, .. } => {
                     let PhysicalPosition { x, y } = position;
-                    cursor_state = Vec2::new(x, y);
+                    game.ecs
+                        .write_resource::<Scene>()
+                        .move_cursor(Vec2::new(x, y));
+                }
+
+                WindowEvent::MouseInput { state, button, .. } => {
+                    use winit::event::{ElementState, MouseButton};
+                    if button == MouseButton::Left {
+                        let mut input = game.ecs.write_resource::<Input>();
+                        let held = state == ElementState::Pressed;
+                        input.clicked |= held && !input.pressed;
+                        input.pressed = held;
+                    } else if button == MouseButton::Right && state == ElementState::Pressed {
+                        game.ecs.write_resource::<Input>().right_clicked = true;
+                    }
+                }
+
+                WindowEvent::MouseWheel { delta, .. } => {
+                    use winit::event::MouseScrollDelta;
+                    // NOTE: pixel deltas come from touchpads, roughly 20 pixels a line.
+                    let lines = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                    };
+                    game.ecs.write_resource::<Input>().scroll += lines;
                 }
 
                 _ => {}
@@ -182,22 +441,64 @@ pub fn play() {
 
             Event::RedrawRequested(_) => {
                 let frame = game.ecs.read_resource::<Frame>().0 + 1;
-                if frame % 100 == 0 {
-                    // println!("");
-                    // println!("frame {}", frame);
-                }
+                clock.frame(Instant::now());
 
                 // TODO: universe_position
                 *game.ecs.write_resource::<Frame>() = Frame(frame);
-                *game.ecs.write_resource::<Scene>() =
-                    Scene::new(window.dimensions().into(), cursor_state);
+                *game.ecs.write_resource::<FrameRate>() = clock.rate();
+
+                // NOTE: the canvas is resized with CSS, which doesn't fire any
+                // resize events, so it's size is checked every frame instead.
+                #[cfg(feature = "web")]
+                {
+                    let size = window.dimensions().into();
+                    let mut scene = game.ecs.write_resource::<Scene>();
+                    if *scene.screen_dimentions() != size {
+                        scene.resize(size, scale_factor(&window, &settings));
+                    }
+                }
 
                 game.tick();
                 window.on_event(window::Event::Draw);
+
+                let mut input = game.ecs.write_resource::<Input>();
+                input.clicked = false;
+                input.right_clicked = false;
+                input.scroll = 0.0;
             }
 
+            // NOTE: frames are capped by waiting until the next one is due.
+            #[cfg(feature = "nat")]
             Event::MainEventsCleared => {
-                window.winit_window().request_redraw();
+                let now = Instant::now();
+                clock.tick(now);
+                match clock.wait(now) {
+                    Some(next_frame) => *control_flow = ControlFlow::WaitUntil(next_frame),
+                    None => {
+                        *control_flow = ControlFlow::Poll;
+                        window.winit_window().request_redraw();
+                    }
+                }
+            }
+
+            // NOTE: the browser asks for frames at the refresh rate of the
+            // screen, and frames are capped by skipping some of them.
+            #[cfg(feature = "web")]
+            Event::MainEventsCleared => {
+                clock.tick(Instant::now());
+                if !frame_requested {
+                    clock::request_animation_frame(&proxy);
+                    frame_requested = true;
+                }
+                *control_flow = ControlFlow::Wait;
+            }
+
+            #[cfg(feature = "web")]
+            Event::UserEvent(()) => {
+                frame_requested = false;
+                if clock.wait(Instant::now()).is_none() {
+                    window.winit_window().request_redraw();
+                }
             }
 
             Event::LoopDestroyed => {}
@@ -206,3 +507,19 @@ pub fn play() {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_factor_test() {
+        let scene = Scene::new(Vec2::new(800, 600), Vec2::new(400, 300), 2.0);
+        assert_eq!(scene.logical_dimensions(), Vec2::new(400.0, 300.0));
+        assert_eq!(scene.logical_cursor(), Vec2::new(200.0, 150.0));
+
+        // NOTE: the bottom right corner in logical pixels is the corner of clip-space.
+        let corner = scene.screen_transform() * Vec4::new(400.0, 300.0, 0.0, 1.0);
+        assert!((corner.xy() - Vec2::new(1.0, -1.0)).magnitude() < 1e-5);
+    }
+}
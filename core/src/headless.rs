@@ -0,0 +1,193 @@
+//! Offscreen rendering without a window, for testing the renderer on machines
+//! without a GPU or a display server.
+//!
+//! The context is created with OSMesa, which rasterizes in software, so it
+//! only needs `libOSMesa` to be installed.
+
+use crate::render::{read_pixels, Renderer};
+use crate::Error;
+
+use glutin::platform::unix::HeadlessContextExt;
+use glutin::{Api, GlProfile, GlRequest, PossiblyCurrent};
+use image::RgbaImage;
+use vek::*;
+
+/// Offscreen context with a fixed size framebuffer, which renderers built
+/// with it draw into.
+pub struct Headless {
+    _context: glutin::Context<PossiblyCurrent>,
+
+    /// Separate handle to the context, for reading frames back after they
+    /// were drawn by the renderer.
+    gl: glow::Context,
+    size: Vec2<u32>,
+}
+
+impl Headless {
+    pub fn new(size: Vec2<u32>) -> Result<(Self, Renderer), Error> {
+        let error = |err: &dyn std::fmt::Display| Error::ContextError(err.to_string());
+
+        let context = glutin::ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+            .with_gl_profile(GlProfile::Core)
+            .with_depth_buffer(24)
+            .build_osmesa(glutin::dpi::PhysicalSize::new(size.x, size.y))
+            .map_err(|err| error(&err))?;
+        let context = unsafe { context.make_current() }.map_err(|(_, err)| error(&err))?;
+
+        let loader = |s: &str| context.get_proc_address(s) as *const _;
+        let gl = glow::Context::from_loader_function(loader);
+        let renderer = Renderer::new(glow::Context::from_loader_function(loader))?;
+
+        let headless = Self {
+            _context: context,
+            gl,
+            size,
+        };
+        Ok((headless, renderer))
+    }
+
+    pub fn size(&self) -> Vec2<u32> {
+        self.size
+    }
+
+    /// Reads back the last frame that was drawn.
+    pub fn frame(&self) -> RgbaImage {
+        read_pixels(&self.gl, self.size)
+    }
+}
+
+/// Fraction of pixels which differ by more than `tolerance` in any channel,
+/// where images of different sizes are entirely different.
+pub fn difference(a: &RgbaImage, b: &RgbaImage, tolerance: u8) -> f32 {
+    if a.dimensions() != b.dimensions() {
+        return 1.0;
+    }
+
+    let different = a
+        .pixels()
+        .zip(b.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0.iter())
+                .any(|(a, b)| (*a as i16 - *b as i16).abs() > tolerance as i16)
+        })
+        .count();
+    different as f32 / (a.width() * a.height()).max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::*;
+    use crate::game::Scene;
+    use crate::state::GameState;
+    use specs::prelude::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    const SIZE: Vec2<u32> = Vec2 { x: 256, y: 192 };
+
+    /// Renders a single frame of a world, or returns `None` when there is no
+    /// OSMesa to create a context with, unless `MINRUSTY_REQUIRE_GL` is set,
+    /// which CI sets so that golden tests can't be skipped silently.
+    fn render(build: impl FnOnce(&mut GameState)) -> Option<RgbaImage> {
+        let (headless, renderer) = match Headless::new(SIZE) {
+            Ok(context) => context,
+            Err(err) if std::env::var_os("MINRUSTY_REQUIRE_GL").is_none() => {
+                // NOTE: the test harness only captures the print macros, so
+                // writing to stderr directly shows the skip in passing runs.
+                let message = format!("warning: skipping render test, no OSMesa: {:?}\n", err);
+                let _ = std::io::stderr().write_all(message.as_bytes());
+                return None;
+            }
+            Err(err) => panic!("no headless context: {:?}", err),
+        };
+
+        let mut game = GameState::new(renderer);
+        // NOTE: the cursor is kept off screen, so it's highlight isn't drawn.
+        let cursor = Vec2::broadcast(-1000);
+        *game.ecs.write_resource::<Scene>() = Scene::new(SIZE.numcast().unwrap(), cursor, 1.0);
+        build(&mut game);
+        game.tick();
+
+        Some(headless.frame())
+    }
+
+    /// Compares a frame with it's reference in `tests/golden`, where setting
+    /// `MINRUSTY_BLESS` replaces the reference with the frame instead.
+    fn assert_golden(name: &str, frame: &RgbaImage) {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+            .iter()
+            .collect();
+
+        if std::env::var_os("MINRUSTY_BLESS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            frame.save(&path).unwrap();
+            return;
+        }
+
+        let reference = image::open(&path)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "missing reference {:?}, run with MINRUSTY_BLESS=1: {}",
+                    path, err
+                )
+            })
+            .to_rgba();
+
+        // NOTE: software rasterizers round slightly differently between versions.
+        let difference = difference(frame, &reference, 4);
+        if difference > 0.001 {
+            let actual = path.with_extension("actual.png");
+            frame.save(&actual).unwrap();
+            panic!("{:?} differs by {}, see {:?}", path, difference, actual);
+        }
+    }
+
+    #[test]
+    fn difference_test() {
+        let a = RgbaImage::from_pixel(4, 4, image::Rgba([10, 20, 30, 255]));
+        let mut b = a.clone();
+        assert_eq!(difference(&a, &b, 0), 0.0);
+
+        b.put_pixel(0, 0, image::Rgba([12, 20, 30, 255]));
+        assert_eq!(difference(&a, &b, 2), 0.0);
+        assert_eq!(difference(&a, &b, 1), 1.0 / 16.0);
+
+        assert_eq!(difference(&a, &RgbaImage::new(2, 2), 255), 1.0);
+    }
+
+    #[test]
+    fn empty_world_is_cleared() {
+        // NOTE: the interface is drawn over the rest of the screen, so only
+        // the rows above it are checked, where it's white title blends in.
+        if let Some(frame) = render(|_| {}) {
+            let mut top = frame.rows().take(20).flatten();
+            assert!(top.all(|pixel| pixel.0.iter().all(|&channel| channel >= 240)));
+        }
+    }
+
+    #[test]
+    fn block_grid_golden() {
+        let frame = render(|game| {
+            let names = ["a", "b", "c", "d"];
+            for i in 0..5 {
+                for j in 0..5 {
+                    game.ecs
+                        .create_entity()
+                        .with(Name(names[((i + j) % 4) as usize].into()))
+                        .with(Coordinate(Vec2::new(i, j)))
+                        .with(TextureIndex(None))
+                        .with(Color(Rgba::white()))
+                        .with(Tile)
+                        .build();
+                }
+            }
+        });
+
+        if let Some(frame) = frame {
+            assert_golden("block_grid.png", &frame);
+        }
+    }
+}
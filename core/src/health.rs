@@ -0,0 +1,345 @@
+//! Durability of blocks, which take damage through events, and are destroyed
+//! into debris once their health runs out.
+
+use crate::components::*;
+use crate::items::{Inventory, ItemEvent, Stack};
+use crate::render::{Layer, WHITE};
+use crate::state::DeltaTime;
+
+use shrev::{EventChannel, ReaderId};
+use specs::prelude::*;
+use specs::Component;
+use vek::*;
+
+/// Share of a hit which always gets through armor.
+const MIN_DAMAGE: f32 = 0.25;
+
+/// Share of their contents destroyed blocks drop into their debris.
+const DROP: f32 = 0.5;
+
+/// Seconds debris takes to fade away.
+const DEBRIS_LIFETIME: f32 = 10.0;
+
+const DEBRIS: Rgba<f32> = Rgba {
+    r: 0.3,
+    g: 0.28,
+    b: 0.25,
+    a: 0.8,
+};
+
+/// Health of a block, which is destroyed once it runs out.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    current: f32,
+    max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    /// Share of health left, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0.0 {
+            (self.current / self.max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.current < self.max
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// Takes damage, and returns the health which was lost.
+    pub fn damage(&mut self, amount: f32) -> f32 {
+        let lost = amount.clamp(0.0, self.current.max(0.0));
+        self.current -= lost;
+        lost
+    }
+
+    pub fn repair(&mut self, amount: f32) {
+        self.current = (self.current + amount.max(0.0)).min(self.max);
+    }
+}
+
+/// Flat reduction of every hit a block takes.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Armor(pub f32);
+
+impl Armor {
+    /// Damage which gets through, where armor never stops a hit completely.
+    pub fn reduce(&self, amount: f32) -> f32 {
+        (amount - self.0).max(amount * MIN_DAMAGE)
+    }
+}
+
+/// Request to damage an entity, where `source` is whatever dealt it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: Option<Entity>,
+}
+
+/// Entity which was destroyed, with the debris it left behind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DestroyedEvent {
+    pub entity: Entity,
+    pub source: Option<Entity>,
+    pub debris: Option<Entity>,
+}
+
+/// What's left of a destroyed block, which holds what it dropped and fades
+/// away.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Debris {
+    age: f32,
+}
+
+/// System which applies damage, destroys entities without health left, and
+/// clears away old debris.
+#[derive(Default)]
+pub struct DamageSystem {
+    reader_id: Option<ReaderId<DamageEvent>>,
+}
+
+impl<'a> System<'a> for DamageSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, LazyUpdate>,
+        Read<'a, EventChannel<DamageEvent>>,
+        Write<'a, EventChannel<DestroyedEvent>>,
+        Write<'a, EventChannel<ItemEvent>>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Inventory>,
+        WriteStorage<'a, Debris>,
+        WriteStorage<'a, Color>,
+        ReadStorage<'a, Armor>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Footprint>,
+        ReadStorage<'a, Rotation>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader_id = Some(
+            world
+                .write_resource::<EventChannel<DamageEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            lazy,
+            damages,
+            mut destroyed,
+            mut items,
+            mut healths,
+            mut inventories,
+            mut debris,
+            mut colors,
+            armors,
+            coordinates,
+            footprints,
+            rotations,
+        ): Self::SystemData,
+    ) {
+        let dt = delta.0.as_secs_f32();
+
+        for event in damages.read(self.reader_id.as_mut().unwrap()) {
+            let target = event.target;
+            if !entities.is_alive(target) {
+                continue;
+            }
+            let health = match healths.get_mut(target) {
+                Some(health) if !health.is_destroyed() => health,
+                _ => continue,
+            };
+            let amount = match armors.get(target) {
+                Some(armor) => armor.reduce(event.amount),
+                None => event.amount,
+            };
+            health.damage(amount);
+            if !health.is_destroyed() {
+                continue;
+            }
+
+            // NOTE: the rest of the contents are lost along with the block.
+            let dropped: Vec<Stack> = inventories
+                .get_mut(target)
+                .map(|inventory| {
+                    let held: Vec<Stack> = inventory.iter().collect();
+                    inventory.remove(&held);
+                    held.into_iter()
+                        .map(|stack| Stack::new(stack.item, (stack.amount as f32 * DROP) as u32))
+                        .filter(|stack| stack.amount > 0)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            // NOTE: only blocks leave debris, which isn't a tile, so it
+            // doesn't get in the way of building over it.
+            let remains = coordinates.get(target).map(|coordinate| {
+                let capacity = dropped.iter().map(|stack| stack.amount).max();
+                let mut inventory = Inventory::new(capacity.unwrap_or(0));
+                inventory.insert(&dropped);
+                lazy.create_entity(&entities)
+                    .with(Debris::default())
+                    .with(coordinate.clone())
+                    .with(footprints.get(target).copied().unwrap_or_default())
+                    .with(rotations.get(target).copied().unwrap_or_default())
+                    .with(TextureIndex(Some(WHITE)))
+                    .with(Color(DEBRIS))
+                    .with(RenderLayer(Layer::Effects))
+                    .with(inventory)
+                    .build()
+            });
+            if let Some(remains) = remains {
+                items.iter_write(dropped.into_iter().map(|stack| ItemEvent {
+                    stack,
+                    from: Some(target),
+                    to: Some(remains),
+                }));
+            }
+
+            destroyed.single_write(DestroyedEvent {
+                entity: target,
+                source: event.source,
+                debris: remains,
+            });
+            let _ = entities.delete(target);
+        }
+
+        for (entity, debris, color) in (&entities, &mut debris, &mut colors).join() {
+            debris.age += dt;
+            let left = 1.0 - debris.age / DEBRIS_LIFETIME;
+            color.0.a = DEBRIS.a * left.max(0.0);
+            if debris.age >= DEBRIS_LIFETIME {
+                let _ = entities.delete(entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::Items;
+    use instant::Duration;
+
+    fn world() -> (World, DamageSystem) {
+        let mut world = World::new();
+        let mut system = DamageSystem::default();
+        System::setup(&mut system, &mut world);
+        world.register::<TextureIndex>();
+        world.register::<RenderLayer>();
+        world.insert(DeltaTime(Duration::from_secs(1)));
+        (world, system)
+    }
+
+    fn damage(world: &mut World, system: &mut DamageSystem, target: Entity, amount: f32) {
+        world
+            .write_resource::<EventChannel<DamageEvent>>()
+            .single_write(DamageEvent {
+                target,
+                amount,
+                source: None,
+            });
+        system.run_now(world);
+        world.maintain();
+    }
+
+    #[test]
+    fn health_test() {
+        let mut health = Health::new(10.0);
+        assert!(!health.is_damaged());
+        assert_eq!(health.damage(4.0), 4.0);
+        assert_eq!(health.fraction(), 0.6);
+        health.repair(100.0);
+        assert_eq!(health.current(), 10.0);
+        assert_eq!(health.damage(100.0), 10.0);
+        assert!(health.is_destroyed());
+
+        assert_eq!(Armor(3.0).reduce(10.0), 7.0);
+        assert_eq!(Armor(30.0).reduce(10.0), 2.5);
+    }
+
+    #[test]
+    fn destruction_test() {
+        let (mut world, mut system) = world();
+        let mut reader = world
+            .write_resource::<EventChannel<DestroyedEvent>>()
+            .register_reader();
+        let stone = Items::default().find("stone").unwrap();
+        let mut inventory = Inventory::new(10);
+        inventory.insert(&[Stack::new(stone, 5)]);
+        let block = world
+            .create_entity()
+            .with(Health::new(10.0))
+            .with(Armor(2.0))
+            .with(Coordinate(Vec2::new(1, 1)))
+            .with(Color(Rgba::white()))
+            .with(inventory)
+            .build();
+
+        damage(&mut world, &mut system, block, 6.0);
+        let health = world.read_storage::<Health>().get(block).copied().unwrap();
+        assert_eq!(health.current(), 6.0);
+
+        // NOTE: damage to destroyed entities is ignored.
+        damage(&mut world, &mut system, block, 8.0);
+        damage(&mut world, &mut system, block, 8.0);
+        assert!(!world.is_alive(block));
+
+        let events: Vec<_> = world
+            .read_resource::<EventChannel<DestroyedEvent>>()
+            .read(&mut reader)
+            .copied()
+            .collect();
+        assert_eq!(events.len(), 1);
+        let remains = events[0].debris.unwrap();
+        let inventories = world.read_storage::<Inventory>();
+        assert_eq!(inventories.get(remains).unwrap().count(stone), 2);
+        assert!(world.read_storage::<Debris>().contains(remains));
+    }
+
+    #[test]
+    fn debris_test() {
+        let (mut world, mut system) = world();
+        let remains = world
+            .create_entity()
+            .with(Debris::default())
+            .with(Color(DEBRIS))
+            .build();
+
+        system.run_now(&world);
+        world.maintain();
+        let alpha = world.read_storage::<Color>().get(remains).unwrap().0.a;
+        assert!(alpha < DEBRIS.a);
+
+        for _ in 0..DEBRIS_LIFETIME as usize {
+            system.run_now(&world);
+            world.maintain();
+        }
+        assert!(!world.is_alive(remains));
+    }
+}
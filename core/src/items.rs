@@ -0,0 +1,432 @@
+//! Items, which are defined as data, and the inventories of blocks and units
+//! which hold them, where every movement of items is sent as an event.
+
+use crate::components::*;
+use crate::Error;
+
+use serde::Deserialize;
+use shrev::EventChannel;
+use specs::prelude::*;
+use specs::Component;
+use std::collections::BTreeMap;
+use vek::*;
+
+/// Item definitions, which are bundled with the game.
+static ITEMS: &str = include_str!("../assets/items.json");
+
+/// Furthest distance in tiles at which units reach into other inventories.
+pub const REACH: f32 = 1.5;
+
+/// Index of an item definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemId(pub u16);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDef {
+    pub name: String,
+
+    /// Color of the item, which tints it's sprite.
+    pub color: [f32; 4],
+}
+
+impl ItemDef {
+    pub fn color(&self) -> Rgba<f32> {
+        Rgba::from(self.color)
+    }
+}
+
+/// Resource of every item definition.
+#[derive(Clone, Debug)]
+pub struct Items {
+    defs: Vec<ItemDef>,
+}
+
+impl Default for Items {
+    fn default() -> Self {
+        Self::parse(ITEMS).expect("Bundled items are valid.")
+    }
+}
+
+impl Items {
+    /// Parses a JSON list of item definitions, where names must be unique.
+    pub fn parse(json: &str) -> Result<Self, Error> {
+        let error = |message| Error::DataError(format!("items: {}", message));
+        let defs: Vec<ItemDef> =
+            serde_json::from_str(json).map_err(|err| error(err.to_string()))?;
+
+        for (i, def) in defs.iter().enumerate() {
+            if def.name.is_empty() {
+                return Err(error(format!("item {} has no name", i)));
+            }
+            if defs[..i].iter().any(|other| other.name == def.name) {
+                return Err(error(format!("item `{}` is defined twice", def.name)));
+            }
+        }
+        if defs.len() > u16::MAX as usize {
+            return Err(error("too many items".into()));
+        }
+        Ok(Self { defs })
+    }
+
+    pub fn find(&self, name: &str) -> Option<ItemId> {
+        self.defs
+            .iter()
+            .position(|def| def.name == name)
+            .map(|i| ItemId(i as u16))
+    }
+
+    pub fn get(&self, item: ItemId) -> &ItemDef {
+        &self.defs[item.0 as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ItemId, &ItemDef)> {
+        self.defs
+            .iter()
+            .enumerate()
+            .map(|(i, def)| (ItemId(i as u16), def))
+    }
+}
+
+/// An amount of a single item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stack {
+    pub item: ItemId,
+    pub amount: u32,
+}
+
+impl Stack {
+    pub fn new(item: ItemId, amount: u32) -> Self {
+        Self { item, amount }
+    }
+}
+
+/// Total amount of every item in a list of stacks, which may repeat items.
+fn totals(stacks: &[Stack]) -> BTreeMap<ItemId, u32> {
+    let mut totals = BTreeMap::new();
+    for stack in stacks {
+        *totals.entry(stack.item).or_insert(0) += stack.amount;
+    }
+    totals
+}
+
+/// Items held by a block or a unit, where every item has it's own capacity.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct Inventory {
+    items: BTreeMap<ItemId, u32>,
+
+    /// Capacity of items without a limit.
+    capacity: u32,
+    limits: BTreeMap<ItemId, u32>,
+}
+
+impl Inventory {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the capacity of a single item, where 0 refuses it.
+    pub fn with_limit(mut self, item: ItemId, limit: u32) -> Self {
+        self.limits.insert(item, limit);
+        self
+    }
+
+    pub fn limit(&self, item: ItemId) -> u32 {
+        self.limits.get(&item).copied().unwrap_or(self.capacity)
+    }
+
+    pub fn count(&self, item: ItemId) -> u32 {
+        self.items.get(&item).copied().unwrap_or(0)
+    }
+
+    /// Amount of an item which still fits.
+    pub fn space(&self, item: ItemId) -> u32 {
+        self.limit(item).saturating_sub(self.count(item))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Items held, in the order they're defined.
+    pub fn iter(&self) -> impl Iterator<Item = Stack> + '_ {
+        self.items
+            .iter()
+            .map(|(&item, &amount)| Stack::new(item, amount))
+    }
+
+    pub fn can_insert(&self, stacks: &[Stack]) -> bool {
+        totals(stacks)
+            .iter()
+            .all(|(&item, &amount)| amount <= self.space(item))
+    }
+
+    pub fn can_remove(&self, stacks: &[Stack]) -> bool {
+        totals(stacks)
+            .iter()
+            .all(|(&item, &amount)| amount <= self.count(item))
+    }
+
+    /// Inserts every stack, or nothing if any of them doesn't fit.
+    pub fn insert(&mut self, stacks: &[Stack]) -> bool {
+        if !self.can_insert(stacks) {
+            return false;
+        }
+        for stack in stacks.iter().filter(|stack| stack.amount > 0) {
+            *self.items.entry(stack.item).or_insert(0) += stack.amount;
+        }
+        true
+    }
+
+    /// Removes every stack, or nothing if any of them isn't held.
+    pub fn remove(&mut self, stacks: &[Stack]) -> bool {
+        if !self.can_remove(stacks) {
+            return false;
+        }
+        for stack in stacks {
+            if let Some(count) = self.items.get_mut(&stack.item) {
+                *count -= stack.amount;
+                if *count == 0 {
+                    self.items.remove(&stack.item);
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Movement of items, where `from` is `None` for items which were produced,
+/// and `to` is `None` for items which were consumed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ItemEvent {
+    pub stack: Stack,
+    pub from: Option<Entity>,
+    pub to: Option<Entity>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferError {
+    NoInventory(Entity),
+    NotAdjacent,
+    NotEnough,
+    NoSpace,
+}
+
+/// Moves every stack from one inventory to another, or nothing if either
+/// inventory can't take part in all of it.
+pub fn transfer(
+    inventories: &mut WriteStorage<Inventory>,
+    from: Entity,
+    to: Entity,
+    stacks: &[Stack],
+) -> Result<(), TransferError> {
+    let source = inventories
+        .get(from)
+        .ok_or(TransferError::NoInventory(from))?;
+    if !source.can_remove(stacks) {
+        return Err(TransferError::NotEnough);
+    }
+    let target = inventories.get(to).ok_or(TransferError::NoInventory(to))?;
+    if from != to && !target.can_insert(stacks) {
+        return Err(TransferError::NoSpace);
+    }
+
+    // NOTE: both inventories were checked, so neither of these can fail.
+    inventories.get_mut(from).unwrap().remove(stacks);
+    inventories.get_mut(to).unwrap().insert(stacks);
+    Ok(())
+}
+
+/// Where an entity is, for checking whether it reaches another one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Place {
+    Tile(Vec2<i32>),
+    Unit(Vec2<f32>),
+}
+
+impl Place {
+    fn of(
+        entity: Entity,
+        coordinates: &ReadStorage<Coordinate>,
+        positions: &ReadStorage<Position>,
+    ) -> Option<Self> {
+        match (coordinates.get(entity), positions.get(entity)) {
+            (Some(coordinate), _) => Some(Place::Tile(coordinate.0)),
+            (None, Some(position)) => Some(Place::Unit(position.0.xy())),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether two places are next to each other, where tiles only touch the
+    /// four tiles around them, and units reach anything close enough.
+    pub fn adjacent(self, other: Self) -> bool {
+        let center = |place| match place {
+            Place::Tile(coordinate) => coordinate.numcast::<f32>().unwrap(),
+            Place::Unit(position) => position,
+        };
+        match (self, other) {
+            (Place::Tile(a), Place::Tile(b)) => (a - b).map(i32::abs).sum() == 1,
+            _ => center(self).distance(center(other)) <= REACH,
+        }
+    }
+}
+
+/// Request to move items between two inventories.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transfer {
+    pub from: Entity,
+    pub to: Entity,
+    pub stacks: Vec<Stack>,
+}
+
+/// Resource of the transfers requested this frame.
+#[derive(Default)]
+pub struct Transfers(pub Vec<Transfer>);
+
+/// System which moves items between adjacent inventories, as requested.
+#[derive(Default)]
+pub struct InventorySystem;
+
+impl<'a> System<'a> for InventorySystem {
+    type SystemData = (
+        Write<'a, Transfers>,
+        Write<'a, EventChannel<ItemEvent>>,
+        WriteStorage<'a, Inventory>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(
+        &mut self,
+        (mut transfers, mut events, mut inventories, coordinates, positions): Self::SystemData,
+    ) {
+        // NOTE: failed transfers are dropped, and have to be requested again.
+        for Transfer { from, to, stacks } in transfers.0.drain(..) {
+            let places = (
+                Place::of(from, &coordinates, &positions),
+                Place::of(to, &coordinates, &positions),
+            );
+            let result = match places {
+                (Some(a), Some(b)) if a.adjacent(b) => {
+                    transfer(&mut inventories, from, to, &stacks)
+                }
+                _ => Err(TransferError::NotAdjacent),
+            };
+            if result.is_ok() {
+                events.iter_write(stacks.into_iter().map(|stack| ItemEvent {
+                    stack,
+                    from: Some(from),
+                    to: Some(to),
+                }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COPPER: ItemId = ItemId(0);
+    const IRON: ItemId = ItemId(1);
+
+    #[test]
+    fn items_test() {
+        let items = Items::default();
+        let copper = items.find("copper-ore").unwrap();
+        assert_eq!(items.get(copper).name, "copper-ore");
+        assert_eq!(items.find("unobtainium"), None);
+
+        let twice =
+            r#"[{ "name": "a", "color": [1, 1, 1, 1] }, { "name": "a", "color": [1, 1, 1, 1] }]"#;
+        assert!(Items::parse(twice).is_err());
+    }
+
+    #[test]
+    fn inventory_test() {
+        let mut inventory = Inventory::new(10).with_limit(IRON, 0);
+        assert_eq!((inventory.limit(COPPER), inventory.limit(IRON)), (10, 0));
+
+        assert!(inventory.insert(&[Stack::new(COPPER, 6)]));
+        // NOTE: stacks of the same item add up, so together these don't fit.
+        assert!(!inventory.insert(&[Stack::new(COPPER, 3), Stack::new(COPPER, 3)]));
+        assert!(!inventory.insert(&[Stack::new(COPPER, 1), Stack::new(IRON, 1)]));
+        assert_eq!(inventory.count(COPPER), 6);
+
+        assert!(!inventory.remove(&[Stack::new(COPPER, 7)]));
+        assert!(inventory.remove(&[Stack::new(COPPER, 6)]));
+        assert!(inventory.is_empty());
+    }
+
+    #[test]
+    fn transfer_test() {
+        let mut world = World::new();
+        let mut system = InventorySystem;
+        System::setup(&mut system, &mut world);
+        world.register::<Coordinate>();
+        let mut reader = world
+            .write_resource::<EventChannel<ItemEvent>>()
+            .register_reader();
+
+        let mut full = Inventory::new(10);
+        full.insert(&[Stack::new(COPPER, 5), Stack::new(IRON, 5)]);
+        let block = |world: &mut World, x, inventory| {
+            world
+                .create_entity()
+                .with(Coordinate(Vec2::new(x, 0)))
+                .with(inventory)
+                .build()
+        };
+        let source = block(&mut world, 0, full);
+        let next = block(&mut world, 1, Inventory::new(10).with_limit(IRON, 4));
+        let far = block(&mut world, 3, Inventory::new(10));
+
+        let mut request = |world: &mut World, to, stacks: Vec<Stack>| {
+            let from = source;
+            world
+                .write_resource::<Transfers>()
+                .0
+                .push(Transfer { from, to, stacks });
+            system.run_now(world);
+        };
+
+        // NOTE: iron doesn't fit, so copper doesn't move either.
+        request(
+            &mut world,
+            next,
+            vec![Stack::new(COPPER, 2), Stack::new(IRON, 5)],
+        );
+        request(&mut world, far, vec![Stack::new(COPPER, 2)]);
+        request(
+            &mut world,
+            next,
+            vec![Stack::new(COPPER, 2), Stack::new(IRON, 4)],
+        );
+
+        let inventories = world.read_storage::<Inventory>();
+        assert_eq!(inventories.get(source).unwrap().count(COPPER), 3);
+        assert_eq!(inventories.get(next).unwrap().count(IRON), 4);
+        assert!(inventories.get(far).unwrap().is_empty());
+
+        let events = world.read_resource::<EventChannel<ItemEvent>>();
+        let moved: Vec<_> = events.read(&mut reader).copied().collect();
+        assert_eq!(
+            moved,
+            vec![
+                ItemEvent {
+                    stack: Stack::new(COPPER, 2),
+                    from: Some(source),
+                    to: Some(next),
+                },
+                ItemEvent {
+                    stack: Stack::new(IRON, 4),
+                    from: Some(source),
+                    to: Some(next),
+                },
+            ]
+        );
+    }
+}
@@ -4,19 +4,52 @@ extern crate memoffset;
 mod builder;
 mod clock;
 mod components;
+mod construction;
+mod conveyor;
+mod crafting;
+mod debug;
+mod drill;
 mod error;
+mod fluids;
 pub mod game;
+#[cfg(all(feature = "nat", target_os = "linux"))]
+pub mod headless;
+mod health;
+mod items;
 mod loader;
 mod logger;
 mod map;
 mod physics;
+mod power;
+mod profiler;
 mod render;
+pub mod settings;
 mod state;
+mod ui;
 mod units;
 mod window;
+mod worldgen;
 
 #[cfg(feature = "web")]
 mod main_web;
 
+pub use components::{Direction, Tile};
+pub use construction::{BuildCosts, ConstructionSite, ConstructionSystem};
+pub use conveyor::{spawn_conveyor, Conveyor, ConveyorSystem};
+pub use crafting::{Factory, FactorySystem, Recipes};
+pub use drill::{Drill, DrillSystem};
 pub use error::Error;
+pub use fluids::{FluidSystem, FluidTank, Fluids};
 use game::*;
+#[cfg(all(feature = "nat", target_os = "linux"))]
+pub use headless::Headless;
+pub use health::{Armor, DamageEvent, DamageSystem, Debris, DestroyedEvent, Health};
+pub use items::ItemId;
+pub use map::{Cell, Map};
+pub use power::{PowerGrid, PowerSystem};
+pub use render::{
+    Align, Blend, BlendModes, DebugDraw, GlyphAtlas, Instance, Instances, Layer, PostEffect,
+    PostProcessing, Renderer, Space,
+};
+pub use settings::Settings;
+pub use state::DeltaTime;
@@ -47,6 +47,11 @@ impl Loader {
             .map(|(e, _)| e)
     }
 
+    /// Iterates over the names of images, with their index in the texture array.
+    pub fn names(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.names.iter().map(String::as_str).enumerate()
+    }
+
     /// Iterates over dynamic images, to supply the image data itself.
     pub fn iter_images(&self) -> impl Iterator<Item = (usize, &DynamicImage)> {
         self.images.iter().enumerate()
@@ -58,8 +63,23 @@ static PNG_ASSETS: &[(&str, &[u8])] = &[
     ("b", include_bytes!("../assets/b.png")),
     ("c", include_bytes!("../assets/c.png")),
     ("d", include_bytes!("../assets/d.png")),
+    ("conveyor", include_bytes!("../assets/conveyor.png")),
+    ("splitter", include_bytes!("../assets/splitter.png")),
+    ("drill", include_bytes!("../assets/drill.png")),
+    ("heavy-drill", include_bytes!("../assets/heavy-drill.png")),
+    ("factory", include_bytes!("../assets/factory.png")),
+    ("generator", include_bytes!("../assets/generator.png")),
+    ("battery", include_bytes!("../assets/battery.png")),
+    ("power-pole", include_bytes!("../assets/power-pole.png")),
+    ("pipe", include_bytes!("../assets/pipe.png")),
+    ("tank", include_bytes!("../assets/tank.png")),
+    ("pump", include_bytes!("../assets/pump.png")),
+    ("core", include_bytes!("../assets/core.png")),
 ];
 
+/// Font text is drawn with.
+pub static FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+
 /// System for bundling assets and entities together.
 #[derive(Default)]
 pub struct AssetSystem {
@@ -1,5 +1,15 @@
-use minrusty;
+use minrusty::{Error, Settings};
 
 fn main() {
-    minrusty::game::play();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let settings = match Settings::load(&args) {
+        Ok(settings) => settings,
+        Err(Error::SettingsError(message)) => {
+            eprintln!("invalid settings: {}", message);
+            std::process::exit(2);
+        }
+        Err(err) => panic!("{:?}", err),
+    };
+
+    minrusty::game::play(settings);
 }
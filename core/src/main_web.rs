@@ -2,5 +2,5 @@ use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(start)]
 pub fn run() {
-    crate::game::play();
+    crate::game::play(crate::Settings::default());
 }
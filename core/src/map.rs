@@ -1,7 +1,26 @@
 use crate::components::*;
+use crate::worldgen::{Floor, Ore};
 use specs::prelude::*;
 use vek::*;
 
+/// Width and height of a chunk of tiles.
+pub const CHUNK_SIZE: i32 = 16;
+
+/// Coordinate of the chunk which contains a tile coordinate.
+pub fn chunk_coordinate(coordinate: Vec2<i32>) -> Vec2<i32> {
+    coordinate.map(|x| x.div_euclid(CHUNK_SIZE))
+}
+
+/// Tiles next to a footprint, with the direction from the footprint to them.
+pub fn neighbours(tiles: &[Vec2<i32>]) -> impl Iterator<Item = (Vec2<i32>, Direction)> + '_ {
+    tiles.iter().flat_map(move |&tile| {
+        Direction::ALL
+            .iter()
+            .map(move |&direction| (tile + direction.offset(), direction))
+            .filter(move |(neighbour, _)| !tiles.contains(neighbour))
+    })
+}
+
 /// Tile storage.
 pub struct Map<C> {
     /// Stores the kind of tile at a coordinate.
@@ -18,7 +37,7 @@ impl<C: Default> Default for Map<C> {
 }
 
 impl<C: Default> Map<C> {
-    fn with_dimentions(dimentions: Vec2<i32>) -> Self {
+    pub fn with_dimentions(dimentions: Vec2<i32>) -> Self {
         let [width, height] = dimentions.into_array();
         Self {
             width,
@@ -27,6 +46,10 @@ impl<C: Default> Map<C> {
         }
     }
 
+    pub fn dimensions(&self) -> Vec2<i32> {
+        Vec2::new(self.width, self.height)
+    }
+
     pub fn get(&self, coordinate: Vec2<i32>) -> Option<&C> {
         if coordinate.x >= 0
             && coordinate.y >= 0
@@ -40,14 +63,37 @@ impl<C: Default> Map<C> {
         }
     }
 
+    pub fn get_mut(&mut self, coordinate: Vec2<i32>) -> Option<&mut C> {
+        if self.get(coordinate).is_some() {
+            Some(&mut self.tiles[((coordinate.y * self.width) + coordinate.x) as usize])
+        } else {
+            None
+        }
+    }
+
     pub fn set(&mut self, coordinate: Vec2<i32>, value: C) {
         self.tiles[((coordinate.y * self.width) + coordinate.x) as usize] = value;
     }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut C> {
+        self.tiles.iter_mut()
+    }
 }
 
+/// What's at a tile of the map, where the ground is kept apart from the block
+/// built on it.
 #[derive(Default)]
 pub struct Cell {
     pub tile: Option<Entity>,
+    pub floor: Floor,
+    pub ore: Option<Ore>,
+}
+
+impl Map<Cell> {
+    /// Block at a coordinate, if there is one.
+    pub fn tile(&self, coordinate: Vec2<i32>) -> Option<Entity> {
+        self.get(coordinate).and_then(|cell| cell.tile)
+    }
 }
 
 #[derive(Default)]
@@ -64,6 +110,8 @@ impl<'a> System<'a> for MappingSystem {
         Write<'a, Map<Cell>>,
         ReadStorage<'a, Coordinate>,
         ReadStorage<'a, Tile>,
+        ReadStorage<'a, Footprint>,
+        ReadStorage<'a, Rotation>,
     );
 
     fn setup(&mut self, world: &mut World) {
@@ -71,7 +119,10 @@ impl<'a> System<'a> for MappingSystem {
         self.reader_id = Some(world.write_storage::<Tile>().register_reader());
     }
 
-    fn run(&mut self, (entities, mut map, coordinates, tiles): Self::SystemData) {
+    fn run(
+        &mut self,
+        (entities, mut map, coordinates, tiles, footprints, rotations): Self::SystemData,
+    ) {
         let Self {
             reader_id,
             modified,
@@ -92,18 +143,36 @@ impl<'a> System<'a> for MappingSystem {
             };
         }
 
-        for (entity, coordinate, _) in (&entities, &coordinates, &*inserted).join() {
-            map.set(coordinate.0, Cell { tile: Some(entity) });
+        // NOTE: removed blocks no longer have a footprint, and modified ones
+        // may have moved, so their cells are looked up across the map.
+        let mut stale = removed.clone();
+        stale |= &*modified;
+        if (&stale).join().next().is_some() {
+            for cell in map.iter_mut() {
+                if matches!(cell.tile, Some(tile) if stale.contains(tile.id())) {
+                    cell.tile = None;
+                }
+            }
         }
 
-        for (entity, coordinate, _) in (&entities, &coordinates, &*modified).join() {
-            map.set(coordinate.0, Cell { tile: Some(entity) });
+        // NOTE: blocks take up every tile of their footprint.
+        for (entity, coordinate, footprint, rotation, _) in (
+            &entities,
+            &coordinates,
+            footprints.maybe(),
+            rotations.maybe(),
+            &*inserted | &*modified,
+        )
+            .join()
+        {
+            let footprint = footprint.copied().unwrap_or_default();
+            let rotation = rotation.copied().unwrap_or_default();
+            for tile in footprint.tiles(coordinate.0, rotation) {
+                if let Some(cell) = map.get_mut(tile) {
+                    cell.tile = Some(entity);
+                }
+            }
         }
-
-        // TODO: tracking removals...?
-        // for (entity, coordinate, _) in (&entities, &coordinates, &*removed).join() {
-        //     map[coordinate.0].tile = None;
-        // }
     }
 }
 
@@ -119,4 +188,41 @@ mod tests {
         assert!(map.get(index).unwrap());
         assert!(!map.get(index + Vec2::new(0, 1)).unwrap());
     }
+
+    #[test]
+    fn chunk_coordinate_test() {
+        assert_eq!(chunk_coordinate(Vec2::new(0, 15)), Vec2::new(0, 0));
+        assert_eq!(chunk_coordinate(Vec2::new(16, 31)), Vec2::new(1, 1));
+        assert_eq!(chunk_coordinate(Vec2::new(-1, -16)), Vec2::new(-1, -1));
+        assert_eq!(chunk_coordinate(Vec2::new(-17, 0)), Vec2::new(-2, 0));
+    }
+
+    #[test]
+    fn removal_test() {
+        let mut world = World::new();
+        let mut system = MappingSystem::default();
+        System::setup(&mut system, &mut world);
+
+        let coordinate = Vec2::new(4, 4);
+        let block = world
+            .create_entity()
+            .with(Tile)
+            .with(Coordinate(coordinate))
+            .with(Footprint(Vec2::new(2, 2)))
+            .build();
+        system.run_now(&world);
+        assert_eq!(
+            world.read_resource::<Map<Cell>>().tile(coordinate),
+            Some(block)
+        );
+        let corner = coordinate + Vec2::new(1, 1);
+        assert_eq!(world.read_resource::<Map<Cell>>().tile(corner), Some(block));
+
+        world.delete_entity(block).unwrap();
+        world.maintain();
+        system.run_now(&world);
+        let map = world.read_resource::<Map<Cell>>();
+        assert!(map.tile(coordinate).is_none());
+        assert!(map.tile(corner).is_none());
+    }
 }
@@ -0,0 +1,521 @@
+//! Power networks, which are made of blocks with power nodes that touch, where
+//! generators produce power, consumers draw it and batteries buffer it.
+
+use crate::components::*;
+use crate::map::{neighbours, Cell, Map};
+use crate::state::DeltaTime;
+
+use specs::prelude::*;
+use specs::Component;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Block which links to the power nodes of the blocks next to it.
+#[derive(Component, Clone, Debug, Default)]
+#[storage(NullStorage)]
+pub struct PowerNode;
+
+#[derive(Component, Clone, Debug)]
+pub struct Generator {
+    /// Power produced every second.
+    pub output: f32,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Consumer {
+    /// Power drawn every second.
+    pub demand: f32,
+
+    /// Fraction of the demand the network satisfied last frame.
+    pub satisfaction: f32,
+}
+
+impl Consumer {
+    pub fn new(demand: f32) -> Self {
+        Self {
+            demand,
+            satisfaction: 0.0,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Battery {
+    pub capacity: f32,
+    pub charge: f32,
+
+    /// Most power charged or discharged every second.
+    pub rate: f32,
+}
+
+impl Battery {
+    pub fn new(capacity: f32, rate: f32) -> Self {
+        Self {
+            capacity,
+            charge: 0.0,
+            rate,
+        }
+    }
+}
+
+pub type NetworkId = u32;
+
+/// Power node in the grid, by entity id.
+#[derive(Debug)]
+struct Node {
+    entity: Entity,
+    network: NetworkId,
+    links: Vec<u32>,
+}
+
+/// Nodes which are linked together, and how much power went through them
+/// last frame.
+#[derive(Clone, Debug, Default)]
+pub struct Network {
+    members: Vec<u32>,
+
+    pub supply: f32,
+    pub demand: f32,
+    pub charge: f32,
+    pub capacity: f32,
+}
+
+/// Resource with the graph of every power node, which is kept up to date as
+/// nodes are placed and removed, instead of being rebuilt.
+#[derive(Debug, Default)]
+pub struct PowerGrid {
+    nodes: HashMap<u32, Node>,
+    networks: HashMap<NetworkId, Network>,
+    next: NetworkId,
+}
+
+impl PowerGrid {
+    /// Network of a power node.
+    pub fn network(&self, entity: Entity) -> Option<NetworkId> {
+        self.nodes
+            .get(&entity.id())
+            .filter(|node| node.entity == entity)
+            .map(|node| node.network)
+    }
+
+    pub fn get(&self, network: NetworkId) -> Option<&Network> {
+        self.networks.get(&network)
+    }
+
+    pub fn len(&self) -> usize {
+        self.networks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    fn create(&mut self, members: Vec<u32>) -> NetworkId {
+        let network = self.next;
+        self.next += 1;
+        for id in &members {
+            self.nodes.get_mut(id).unwrap().network = network;
+        }
+        self.networks.insert(
+            network,
+            Network {
+                members,
+                ..Default::default()
+            },
+        );
+        network
+    }
+
+    /// Adds a node linked to other nodes, which merges their networks into
+    /// the biggest one of them.
+    fn insert(&mut self, entity: Entity, links: &[Entity]) {
+        let mut links: Vec<u32> = links
+            .iter()
+            .filter(|&&link| self.network(link).is_some())
+            .map(|link| link.id())
+            .filter(|&link| link != entity.id())
+            .collect();
+        links.sort_unstable();
+        links.dedup();
+
+        let mut networks: Vec<NetworkId> = links.iter().map(|id| self.nodes[id].network).collect();
+        networks.sort_unstable();
+        networks.dedup();
+        networks.sort_by_key(|network| std::cmp::Reverse(self.networks[network].members.len()));
+
+        let id = entity.id();
+        for link in &links {
+            self.nodes.get_mut(link).unwrap().links.push(id);
+        }
+        self.nodes.insert(
+            id,
+            Node {
+                entity,
+                network: 0,
+                links,
+            },
+        );
+
+        let network = match networks.split_first() {
+            None => {
+                self.create(vec![id]);
+                return;
+            }
+            Some((&network, merged)) => {
+                for other in merged {
+                    let members = self.networks.remove(other).unwrap().members;
+                    for member in &members {
+                        self.nodes.get_mut(member).unwrap().network = network;
+                    }
+                    self.networks
+                        .get_mut(&network)
+                        .unwrap()
+                        .members
+                        .extend(members);
+                }
+                network
+            }
+        };
+        self.nodes.get_mut(&id).unwrap().network = network;
+        self.networks.get_mut(&network).unwrap().members.push(id);
+    }
+
+    /// Removes a node, which splits it's network into every group of nodes
+    /// which are no longer linked together.
+    fn remove(&mut self, id: u32) {
+        let node = match self.nodes.remove(&id) {
+            Some(node) => node,
+            None => return,
+        };
+        for link in &node.links {
+            self.nodes
+                .get_mut(link)
+                .unwrap()
+                .links
+                .retain(|&other| other != id);
+        }
+
+        let mut members = self.networks.remove(&node.network).unwrap().members;
+        members.retain(|&member| member != id);
+        if members.is_empty() {
+            return;
+        }
+
+        // NOTE: every remaining node was linked through the removed one, so
+        // it's reached from one of it's links.
+        let mut seen = HashSet::new();
+        let mut groups: Vec<Vec<u32>> = Vec::new();
+        for &start in &node.links {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut group = vec![start];
+            let mut queue = VecDeque::from(vec![start]);
+            while let Some(current) = queue.pop_front() {
+                for &link in &self.nodes[&current].links {
+                    if seen.insert(link) {
+                        group.push(link);
+                        queue.push_back(link);
+                    }
+                }
+            }
+            groups.push(group);
+        }
+
+        // NOTE: the biggest group keeps the network, to keep it's id stable.
+        groups.sort_unstable_by_key(|group| std::cmp::Reverse(group.len()));
+        let mut groups = groups.into_iter();
+        if let Some(group) = groups.next() {
+            self.networks.insert(
+                node.network,
+                Network {
+                    members: group,
+                    ..Default::default()
+                },
+            );
+        }
+        for group in groups {
+            self.create(group);
+        }
+    }
+}
+
+/// System which keeps the power grid up to date, and balances the power of
+/// every network, where consumers get the same fraction of their demand.
+#[derive(Default)]
+pub struct PowerSystem {
+    reader_id: Option<ReaderId<ComponentEvent>>,
+    inserted: BitSet,
+    modified: BitSet,
+    removed: BitSet,
+}
+
+impl<'a> System<'a> for PowerSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, Map<Cell>>,
+        Write<'a, PowerGrid>,
+        ReadStorage<'a, Tile>,
+        ReadStorage<'a, PowerNode>,
+        ReadStorage<'a, Coordinate>,
+        ReadStorage<'a, Footprint>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Generator>,
+        WriteStorage<'a, Consumer>,
+        WriteStorage<'a, Battery>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader_id = Some(world.write_storage::<Tile>().register_reader());
+    }
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            map,
+            mut grid,
+            tiles,
+            nodes,
+            coordinates,
+            footprints,
+            rotations,
+            generators,
+            mut consumers,
+            mut batteries,
+        ): Self::SystemData,
+    ) {
+        self.inserted.clear();
+        self.modified.clear();
+        self.removed.clear();
+
+        for event in tiles.channel().read(self.reader_id.as_mut().unwrap()) {
+            let _ = match event {
+                ComponentEvent::Inserted(id) => self.inserted.add(*id),
+                ComponentEvent::Modified(id) => self.modified.add(*id),
+                ComponentEvent::Removed(id) => self.removed.add(*id),
+            };
+        }
+
+        // NOTE: moved blocks are taken out of the grid, and put back in.
+        for id in (&self.removed | &self.modified).join() {
+            grid.remove(id);
+        }
+        for (entity, coordinate, footprint, rotation, _, _) in (
+            &entities,
+            &coordinates,
+            footprints.maybe(),
+            rotations.maybe(),
+            &nodes,
+            &self.inserted | &self.modified,
+        )
+            .join()
+        {
+            let footprint = footprint.copied().unwrap_or_default();
+            let rotation = rotation.copied().unwrap_or_default();
+            let tiles: Vec<_> = footprint.tiles(coordinate.0, rotation).collect();
+            let links: Vec<_> = neighbours(&tiles)
+                .filter_map(|(neighbour, _)| map.tile(neighbour))
+                .filter(|&link| nodes.contains(link))
+                .collect();
+            grid.insert(entity, &links);
+        }
+
+        let dt = delta.0.as_secs_f32().max(f32::EPSILON);
+        let PowerGrid {
+            nodes: graph,
+            networks,
+            ..
+        } = &mut *grid;
+        for network in networks.values_mut() {
+            let members: Vec<Entity> = network.members.iter().map(|id| graph[id].entity).collect();
+
+            let supply: f32 = members
+                .iter()
+                .filter_map(|&entity| generators.get(entity))
+                .map(|generator| generator.output)
+                .sum();
+            let demand: f32 = members
+                .iter()
+                .filter_map(|&entity| consumers.get(entity))
+                .map(|consumer| consumer.demand)
+                .sum();
+
+            // NOTE: batteries charge from what's left over, and discharge
+            // what's missing, as much as their rate allows.
+            let mut discharged = 0.0;
+            let mut available: f32 = members
+                .iter()
+                .filter_map(|&entity| batteries.get(entity))
+                .map(|battery| battery.charge.min(battery.rate * dt))
+                .sum();
+            let mut surplus = (supply - demand) * dt;
+            for &entity in &members {
+                let battery = match batteries.get_mut(entity) {
+                    Some(battery) => battery,
+                    None => continue,
+                };
+                if surplus > 0.0 {
+                    let charged = surplus
+                        .min(battery.rate * dt)
+                        .min(battery.capacity - battery.charge);
+                    battery.charge += charged;
+                    surplus -= charged;
+                } else if surplus < 0.0 && available > 0.0 {
+                    let share = battery.charge.min(battery.rate * dt);
+                    let drawn = share * (-surplus / available).min(1.0);
+                    battery.charge -= drawn;
+                    discharged += drawn;
+                    surplus += drawn;
+                    available -= share;
+                }
+            }
+
+            let satisfaction = if demand > 0.0 {
+                ((supply * dt + discharged) / (demand * dt)).min(1.0)
+            } else {
+                1.0
+            };
+            for &entity in &members {
+                if let Some(consumer) = consumers.get_mut(entity) {
+                    consumer.satisfaction = satisfaction;
+                }
+            }
+
+            network.supply = supply;
+            network.demand = demand;
+            network.charge = 0.0;
+            network.capacity = 0.0;
+            for battery in members.iter().filter_map(|&entity| batteries.get(entity)) {
+                network.charge += battery.charge;
+                network.capacity += battery.capacity;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::MappingSystem;
+    use instant::Duration;
+    use vek::*;
+
+    struct Systems(MappingSystem, PowerSystem);
+
+    fn world() -> (World, Systems) {
+        let mut world = World::new();
+        let mut mapping = MappingSystem::default();
+        let mut power = PowerSystem::default();
+        System::setup(&mut mapping, &mut world);
+        System::setup(&mut power, &mut world);
+        world.insert(DeltaTime(Duration::from_secs(1)));
+        (world, Systems(mapping, power))
+    }
+
+    fn run(world: &mut World, systems: &mut Systems) {
+        world.maintain();
+        systems.0.run_now(world);
+        systems.1.run_now(world);
+    }
+
+    fn node(world: &mut World, x: i32, y: i32) -> Entity {
+        world
+            .create_entity()
+            .with(Coordinate(Vec2::new(x, y)))
+            .with(Tile)
+            .with(PowerNode)
+            .build()
+    }
+
+    #[test]
+    fn merge_split_test() {
+        let (mut world, mut systems) = world();
+        let left = node(&mut world, 1, 1);
+        let right = node(&mut world, 3, 1);
+        let below = node(&mut world, 2, 0);
+        run(&mut world, &mut systems);
+        assert_eq!(world.read_resource::<PowerGrid>().len(), 3);
+
+        // NOTE: the middle links every other node together.
+        let middle = node(&mut world, 2, 1);
+        run(&mut world, &mut systems);
+        let grid = world.read_resource::<PowerGrid>();
+        assert_eq!(grid.len(), 1);
+        let network = grid.network(middle).unwrap();
+        for &entity in &[left, right, below] {
+            assert_eq!(grid.network(entity), Some(network));
+        }
+        drop(grid);
+
+        // NOTE: a loop doesn't split when a single link is removed.
+        let corner = node(&mut world, 1, 0);
+        run(&mut world, &mut systems);
+        world.delete_entity(below).unwrap();
+        run(&mut world, &mut systems);
+        let grid = world.read_resource::<PowerGrid>();
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid.network(corner), Some(network));
+        drop(grid);
+
+        world.delete_entity(middle).unwrap();
+        run(&mut world, &mut systems);
+        let grid = world.read_resource::<PowerGrid>();
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid.network(left), grid.network(corner));
+        assert_ne!(grid.network(left), grid.network(right));
+        assert_eq!(grid.network(middle), None);
+    }
+
+    #[test]
+    fn balance_test() {
+        let (mut world, mut systems) = world();
+        let generator = node(&mut world, 1, 1);
+        let battery = node(&mut world, 2, 1);
+        let consumers = [node(&mut world, 3, 1), node(&mut world, 4, 1)];
+        world
+            .write_storage()
+            .insert(generator, Generator { output: 2.0 })
+            .unwrap();
+        world
+            .write_storage()
+            .insert(battery, Battery::new(10.0, 1.0))
+            .unwrap();
+        for &consumer in &consumers {
+            world
+                .write_storage()
+                .insert(consumer, Consumer::new(0.5))
+                .unwrap();
+        }
+
+        // NOTE: the battery charges at it's rate from the surplus.
+        run(&mut world, &mut systems);
+        let charge = |world: &World| world.read_storage::<Battery>().get(battery).unwrap().charge;
+        assert_eq!(charge(&world), 1.0);
+
+        // NOTE: the battery covers a part of the deficit.
+        for &consumer in &consumers {
+            world
+                .write_storage::<Consumer>()
+                .get_mut(consumer)
+                .unwrap()
+                .demand = 2.0;
+        }
+        run(&mut world, &mut systems);
+        assert_eq!(charge(&world), 0.0);
+        let satisfaction = |world: &World, consumer| {
+            world
+                .read_storage::<Consumer>()
+                .get(consumer)
+                .unwrap()
+                .satisfaction
+        };
+        assert_eq!(satisfaction(&world, consumers[0]), 0.75);
+
+        run(&mut world, &mut systems);
+        assert_eq!(satisfaction(&world, consumers[0]), 0.5);
+        assert_eq!(satisfaction(&world, consumers[1]), 0.5);
+    }
+}
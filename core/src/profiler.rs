@@ -0,0 +1,333 @@
+//! Wall-clock profiling of systems, render phases and frames, which keeps
+//! rolling statistics, and can capture spans as a Chrome trace.
+
+use instant::{Duration, Instant};
+use serde::Serialize;
+use specs::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::thread::ThreadId;
+
+/// Number of frames statistics are computed over.
+const HISTORY: usize = 120;
+
+/// What a span measures, which groups spans in trace viewers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Category {
+    System,
+    Render,
+    Frame,
+}
+
+impl Category {
+    fn name(self) -> &'static str {
+        match self {
+            Category::System => "system",
+            Category::Render => "render",
+            Category::Frame => "frame",
+        }
+    }
+}
+
+/// Time spent in something, relative to when the profiler was created.
+#[derive(Clone, Copy, Debug)]
+struct Span {
+    name: &'static str,
+    category: Category,
+    start: Duration,
+    duration: Duration,
+    thread: ThreadId,
+}
+
+/// Statistics of the time spent per frame in something.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub average: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    fn new(durations: &VecDeque<Duration>) -> Self {
+        let mut sorted: Vec<Duration> = durations.iter().copied().collect();
+        sorted.sort();
+        let percentile = |p: f32| {
+            let i = ((sorted.len() - 1) as f32 * p).round() as usize;
+            sorted[i]
+        };
+
+        Self {
+            average: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Resource which systems record spans into, from any thread, and which
+/// is rolled over into statistics at the end of every frame.
+pub struct Profiler {
+    start: Instant,
+
+    /// Spans of the current frame.
+    spans: Mutex<Vec<Span>>,
+
+    /// Time spent per frame by name, over the latest frames.
+    history: HashMap<&'static str, VecDeque<Duration>>,
+
+    /// Every span since the capture started, while capturing.
+    capture: Option<Vec<Span>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            spans: Mutex::new(Vec::new()),
+            history: HashMap::new(),
+            capture: None,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn record(&self, name: &'static str, category: Category, start: Instant, end: Instant) {
+        let span = Span {
+            name,
+            category,
+            start: start.duration_since(self.start),
+            duration: end.duration_since(start),
+            thread: std::thread::current().id(),
+        };
+        self.spans.lock().unwrap().push(span);
+    }
+
+    /// Adds the spans of the frame to the statistics, where spans with the
+    /// same name add up.
+    pub fn end_frame(&mut self) {
+        let spans = std::mem::take(self.spans.get_mut().unwrap());
+
+        let mut totals: HashMap<&'static str, Duration> = HashMap::new();
+        for span in spans.iter() {
+            *totals.entry(span.name).or_default() += span.duration;
+        }
+        for (name, total) in totals {
+            let history = self.history.entry(name).or_default();
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+            history.push_back(total);
+        }
+
+        if let Some(capture) = &mut self.capture {
+            capture.extend(spans);
+        }
+    }
+
+    pub fn stats(&self, name: &str) -> Option<Stats> {
+        self.history
+            .get(name)
+            .filter(|history| !history.is_empty())
+            .map(Stats::new)
+    }
+
+    /// Statistics of everything which was recorded, slowest on average first.
+    pub fn all_stats(&self) -> Vec<(&'static str, Stats)> {
+        let mut stats: Vec<_> = self
+            .history
+            .iter()
+            .filter(|(_, history)| !history.is_empty())
+            .map(|(name, history)| (*name, Stats::new(history)))
+            .collect();
+        stats.sort_by(|a, b| b.1.average.cmp(&a.1.average).then(a.0.cmp(b.0)));
+        stats
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    /// Stops capturing, and returns the captured spans as Chrome trace event
+    /// JSON, which trace viewers like `chrome://tracing` can open.
+    pub fn finish_capture(&mut self) -> Option<String> {
+        let spans = self.capture.take()?;
+
+        #[derive(Serialize)]
+        struct Event {
+            name: &'static str,
+            cat: &'static str,
+            ph: &'static str,
+            ts: f64,
+            dur: f64,
+            pid: u32,
+            tid: usize,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Trace {
+            trace_events: Vec<Event>,
+            display_time_unit: &'static str,
+        }
+
+        // NOTE: thread ids can't be turned into numbers, so they are numbered
+        // in the order they first show up.
+        let mut threads: Vec<ThreadId> = Vec::new();
+        let micros = |duration: Duration| duration.as_secs_f64() * 1e6;
+        let trace_events = spans
+            .iter()
+            .map(|span| {
+                let tid = match threads.iter().position(|&id| id == span.thread) {
+                    Some(tid) => tid,
+                    None => {
+                        threads.push(span.thread);
+                        threads.len() - 1
+                    }
+                };
+                Event {
+                    name: span.name,
+                    cat: span.category.name(),
+                    ph: "X",
+                    ts: micros(span.start),
+                    dur: micros(span.duration),
+                    pid: 0,
+                    tid,
+                }
+            })
+            .collect();
+
+        let trace = Trace {
+            trace_events,
+            display_time_unit: "ms",
+        };
+        Some(serde_json::to_string(&trace).expect("Traces are plain data."))
+    }
+}
+
+/// Writes a trace into the `traces` directory, named by the milliseconds since
+/// the unix epoch, and returns it's path.
+#[cfg(feature = "nat")]
+pub fn save_trace(trace: &str) -> std::io::Result<String> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+
+    std::fs::create_dir_all("traces")?;
+    let path = format!("traces/{}.json", stamp);
+    std::fs::write(&path, trace)?;
+    Ok(path)
+}
+
+/// System which records the time another system takes to run.
+pub struct Profiled<S> {
+    name: &'static str,
+    system: S,
+}
+
+impl<S> Profiled<S> {
+    pub fn new(name: &'static str, system: S) -> Self {
+        Self { name, system }
+    }
+}
+
+impl<'a, S> System<'a> for Profiled<S>
+where
+    S: System<'a>,
+    S::SystemData: SystemData<'a>,
+{
+    type SystemData = (S::SystemData, Read<'a, Profiler>);
+
+    fn run(&mut self, (data, profiler): Self::SystemData) {
+        let start = Instant::now();
+        self.system.run(data);
+        profiler.record(self.name, Category::System, start, Instant::now());
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        <Read<Profiler> as SystemData>::setup(world);
+        self.system.setup(world);
+    }
+}
+
+/// Adds systems to a dispatcher, wrapped to be profiled under their name.
+pub trait WithProfiled<'a> {
+    fn with_profiled<S>(self, system: S, name: &'static str, dependencies: &[&str]) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'a,
+        for<'c> <S as System<'c>>::SystemData: SystemData<'c>;
+}
+
+impl<'a, 'b> WithProfiled<'a> for DispatcherBuilder<'a, 'b> {
+    fn with_profiled<S>(self, system: S, name: &'static str, dependencies: &[&str]) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'a,
+        for<'c> <S as System<'c>>::SystemData: SystemData<'c>,
+    {
+        self.with(Profiled::new(name, system), name, dependencies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(profiler: &mut Profiler, name: &'static str, millis: u64) {
+        let start = profiler.start;
+        let end = start + Duration::from_millis(millis);
+        profiler.record(name, Category::System, start, end);
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut profiler = Profiler::default();
+        for i in 1..=HISTORY as u64 + 20 {
+            frame(&mut profiler, "a", i % 100 + 1);
+            // NOTE: spans of the same name within a frame add up.
+            frame(&mut profiler, "b", 1);
+            frame(&mut profiler, "b", 1);
+            profiler.end_frame();
+        }
+
+        let a = profiler.stats("a").unwrap();
+        assert_eq!(a.max, Duration::from_millis(100));
+        assert!(a.p50 > Duration::from_millis(40) && a.p50 < Duration::from_millis(60));
+        assert!(a.p95 >= Duration::from_millis(90));
+
+        let b = profiler.stats("b").unwrap();
+        assert_eq!(
+            (b.average, b.max),
+            (Duration::from_millis(2), Duration::from_millis(2))
+        );
+
+        assert_eq!(profiler.all_stats()[0].0, "a");
+        assert_eq!(profiler.stats("c"), None);
+    }
+
+    #[test]
+    fn chrome_trace_test() {
+        let mut profiler = Profiler::default();
+        frame(&mut profiler, "ignored", 1);
+        profiler.end_frame();
+
+        profiler.start_capture();
+        frame(&mut profiler, "a", 2);
+        profiler.end_frame();
+        let trace = profiler.finish_capture().unwrap();
+        assert!(!profiler.is_capturing());
+
+        let trace: serde_json::Value = serde_json::from_str(&trace).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["name"], "a");
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["dur"], 2000.0);
+    }
+}
@@ -2,14 +2,15 @@ use super::*;
 
 use glow::*;
 
-pub struct Buffer<P: Pipeline> {
+/// Buffer of elements of type `T`, where sizes and offsets are counted in elements.
+pub struct Buffer<T: Pod> {
     buffer_id: Option<BufferId>,
     buffer_type: u32,
     size: usize,
-    phantom: std::marker::PhantomData<P::Vertex>,
+    phantom: std::marker::PhantomData<T>,
 }
 
-impl<P: Pipeline> Buffer<P> {
+impl<T: Copy + Pod> Buffer<T> {
     /// # SAFETY
     ///
     /// `data` will be read after this function has been called.
@@ -17,7 +18,7 @@ impl<P: Pipeline> Buffer<P> {
     pub unsafe fn immutable(
         gl: &Context,
         buffer_type: u32,
-        data: &[P::Vertex],
+        data: &[T],
     ) -> Result<Self, RenderError> {
         let size = data.len();
         let buffer_id = Some(gl.create_buffer()?);
@@ -44,7 +45,11 @@ impl<P: Pipeline> Buffer<P> {
             // SAFETY: should be safe because it doesn't pass any raw memory.
             buffer_id = Some(gl.create_buffer()?);
             gl.bind_buffer(buffer_type, buffer_id);
-            gl.buffer_data_size(buffer_type, size as _, glow::STREAM_DRAW);
+            gl.buffer_data_size(
+                buffer_type,
+                (size * std::mem::size_of::<T>()) as _,
+                glow::STREAM_DRAW,
+            );
             gl.bind_buffer(buffer_type, None);
         }
 
@@ -56,18 +61,24 @@ impl<P: Pipeline> Buffer<P> {
         })
     }
 
+    /// Number of elements the buffer can hold.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     /// # SAFETY
     ///
     /// `data` will be read after this function has been called.
-    pub unsafe fn update(&self, gl: &Context, index: i32, data: &[P::Vertex]) {
-        assert!(index as usize + data.len() <= self.size);
+    pub unsafe fn update(&self, gl: &Context, index: usize, data: &[T]) {
+        assert!(index + data.len() <= self.size);
 
         let (head, data, tail) = data.align_to::<u8>();
         assert!(head.is_empty());
         assert!(tail.is_empty());
 
+        let offset = index * std::mem::size_of::<T>();
         gl.bind_buffer(self.buffer_type, self.buffer_id);
-        gl.buffer_sub_data_u8_slice(self.buffer_type, index, data);
+        gl.buffer_sub_data_u8_slice(self.buffer_type, offset as _, data);
         gl.bind_buffer(self.buffer_type, None)
     }
 
@@ -0,0 +1,168 @@
+//! Reading frames back from the GPU, for bug reports, golden-image tests and
+//! recording short clips.
+
+use super::*;
+
+use glow::*;
+use image::{DynamicImage, ImageOutputFormat, RgbaImage};
+
+/// Reads the pixels of the bound framebuffer into an image.
+pub fn read_pixels(gl: &Context, size: Vec2<u32>) -> RgbaImage {
+    let [width, height] = size.into_array();
+    let mut data = vec![0; (width * height * 4) as usize];
+    unsafe {
+        gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+        gl.read_pixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            &mut data,
+        );
+    }
+
+    // NOTE: OpenGL reads rows from the bottom up, while images start at the top.
+    let mut image = RgbaImage::from_raw(width, height, data).expect("Buffer is sized to fit.");
+    image::imageops::flip_vertical_in_place(&mut image);
+    image
+}
+
+/// Encodes an image as PNG.
+pub fn encode_png(image: RgbaImage) -> Result<Vec<u8>, RenderError> {
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgba8(image)
+        .write_to(&mut bytes, ImageOutputFormat::PNG)
+        .map_err(|err| RenderError::Capture(err.to_string()))?;
+    Ok(bytes)
+}
+
+/// Frame to be saved as a PNG file at a path.
+#[cfg(feature = "nat")]
+type Frame = (String, RgbaImage);
+
+/// Frames which can wait to be saved, where frames captured while it's full
+/// are dropped, since encoding is slower than drawing.
+#[cfg(feature = "nat")]
+const QUEUE: usize = 8;
+
+/// Resource for requesting captures, which are taken by the renderer right
+/// after a frame was drawn, and saved as PNG files on native.
+#[derive(Default)]
+pub struct Capture {
+    /// Saves the next frame as a screenshot.
+    pub screenshot: bool,
+
+    /// Saves every frame as a numbered sequence, for as long as it's set.
+    pub recording: bool,
+
+    /// Directory of the current recording, and frames saved into it.
+    session: Option<(String, u32)>,
+
+    /// Frames of the current recording which were dropped.
+    #[cfg(feature = "nat")]
+    dropped: u32,
+
+    /// Thread which encodes and writes frames, so that recording doesn't
+    /// stall the renderer, which is started with the first capture.
+    // NOTE: the sender is only shared behind a lock, since resources are
+    // shared across threads.
+    #[cfg(feature = "nat")]
+    writer: Option<std::sync::Mutex<std::sync::mpsc::SyncSender<Frame>>>,
+}
+
+impl Capture {
+    /// Whether the current frame has to be read back.
+    pub fn is_requested(&self) -> bool {
+        self.screenshot || self.recording
+    }
+
+    /// Saves a captured frame according to the requests, and resets them.
+    #[cfg(feature = "nat")]
+    pub fn save(&mut self, image: RgbaImage) -> Result<(), RenderError> {
+        use std::sync::mpsc::TrySendError;
+
+        let stamp = Self::timestamp();
+        let writer = self.writer.get_or_insert_with(Self::spawn_writer);
+        let writer = writer.get_mut().expect("Capture writer isn't poisoned.");
+        let send = |path: String| match writer.try_send((path, image.clone())) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => Ok(false),
+            Err(TrySendError::Disconnected(_)) => {
+                Err(RenderError::Capture("capture writer stopped".into()))
+            }
+        };
+
+        if self.screenshot {
+            self.screenshot = false;
+            if !send(format!("screenshots/{}.png", stamp))? {
+                crate::logger::log("dropped a screenshot, too many captures are being saved");
+            }
+        }
+
+        // NOTE: dropped frames keep their number, so the gaps show in the
+        // recording instead of it speeding up.
+        if self.recording {
+            let (directory, frame) = self
+                .session
+                .get_or_insert_with(|| (format!("recordings/{}", stamp), 0));
+            if !send(format!("{}/{:06}.png", directory, frame))? {
+                self.dropped += 1;
+            }
+            *frame += 1;
+        } else if self.session.take().is_some() && self.dropped > 0 {
+            crate::logger::log(&format!(
+                "dropped {} frames of the recording, which were saved too slowly",
+                self.dropped
+            ));
+            self.dropped = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Starts the thread which saves frames in the order they're sent, until
+    /// the sender is dropped.
+    #[cfg(feature = "nat")]
+    fn spawn_writer() -> std::sync::Mutex<std::sync::mpsc::SyncSender<Frame>> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<Frame>(QUEUE);
+        std::thread::spawn(move || {
+            for (path, image) in receiver {
+                if let Err(err) = Self::write(&path, &image) {
+                    crate::logger::log(&format!("failed to save capture: {:?}", err));
+                }
+            }
+        });
+        std::sync::Mutex::new(sender)
+    }
+
+    /// NOTE: there is no file system on web, so captures have to be taken
+    /// through the renderer and encoded instead.
+    #[cfg(feature = "web")]
+    pub fn save(&mut self, _image: RgbaImage) -> Result<(), RenderError> {
+        self.screenshot = false;
+        self.session = None;
+        Ok(())
+    }
+
+    /// Milliseconds since the unix epoch, so that captures are sorted by time.
+    #[cfg(feature = "nat")]
+    fn timestamp() -> u128 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or(0)
+    }
+
+    #[cfg(feature = "nat")]
+    fn write(path: &str, image: &RgbaImage) -> Result<(), RenderError> {
+        let path = std::path::Path::new(path);
+        let error = |err: std::io::Error| RenderError::Capture(err.to_string());
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(error)?;
+        }
+        image.save(path).map_err(error)
+    }
+}
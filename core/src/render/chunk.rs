@@ -0,0 +1,101 @@
+//! Static tile sprites, which are cached in one batch per map chunk.
+
+use super::*;
+use crate::map::{chunk_coordinate, CHUNK_SIZE};
+
+use glow::*;
+use std::collections::HashMap;
+
+/// Tile sprites grouped by the chunk they're in, so that only chunks with
+/// changed tiles are uploaded, and only chunks on screen are drawn.
+#[derive(Default)]
+pub struct Chunks {
+    batches: HashMap<Vec2<i32>, SpriteBatch>,
+
+    /// Whether the tiles of every chunk are sorted back to front.
+    sorted: bool,
+
+    /// Chunk of every tile, indexed by entity id.
+    locations: Vec<Option<Vec2<i32>>>,
+}
+
+impl Chunks {
+    pub fn new(sorted: bool) -> Self {
+        Self {
+            sorted,
+            ..Default::default()
+        }
+    }
+
+    /// Inserts or replaces the sprite of a tile, moving it to another chunk
+    /// when the tile moved.
+    pub fn insert(
+        &mut self,
+        gl: &Context,
+        quad: &Buffer<Vertex>,
+        id: u32,
+        coordinate: Vec2<i32>,
+        instance: Instance,
+    ) -> Result<(), RenderError> {
+        let chunk = chunk_coordinate(coordinate);
+        if self.location(id) != Some(chunk) {
+            self.remove(id);
+        }
+
+        let batch = match self.batches.get_mut(&chunk) {
+            Some(batch) => batch,
+            None => {
+                let size = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+                let mut batch = SpriteBatch::new(gl, quad, size)?;
+                batch.sorted = self.sorted;
+                self.batches.entry(chunk).or_insert(batch)
+            }
+        };
+        batch.instances.insert(id, instance);
+
+        if self.locations.len() <= id as usize {
+            self.locations.resize(id as usize + 1, None);
+        }
+        self.locations[id as usize] = Some(chunk);
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        let chunk = self.locations.get_mut(id as usize).and_then(Option::take);
+        if let Some(batch) = chunk.and_then(|chunk| self.batches.get_mut(&chunk)) {
+            batch.instances.remove(id);
+        }
+    }
+
+    fn location(&self, id: u32) -> Option<Vec2<i32>> {
+        self.locations.get(id as usize).copied().flatten()
+    }
+
+    /// Uploads the tiles which changed in every chunk.
+    pub fn sync(&mut self, gl: &Context, quad: &Buffer<Vertex>) -> Result<(), RenderError> {
+        for batch in self.batches.values_mut() {
+            batch.sync(gl, quad)?;
+        }
+        Ok(())
+    }
+
+    /// Draws the chunks which overlap with the world-space `bounds`.
+    pub unsafe fn draw(&self, gl: &Context, bounds: Aabr<f32>) {
+        for (chunk, batch) in self.batches.iter() {
+            if Self::bounds(*chunk).collides_with_aabr(bounds) {
+                batch.draw(gl);
+            }
+        }
+    }
+
+    /// World-space rectangle covered by a chunk, where tiles are centered on
+    /// their coordinate.
+    fn bounds(chunk: Vec2<i32>) -> Aabr<f32> {
+        let min = (chunk * CHUNK_SIZE).numcast::<f32>().unwrap() - 0.5;
+        Aabr {
+            min,
+            max: min + CHUNK_SIZE as f32,
+        }
+    }
+}
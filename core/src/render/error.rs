@@ -8,6 +8,15 @@ pub enum RenderError {
 
     /// Data allocated for a statically sized buffer is now full.
     BufferFull,
+
+    /// Framebuffer can't be drawn into, with the status OpenGL gave.
+    FramebufferIncomplete(u32),
+
+    /// Captured frame failed to be encoded or saved.
+    Capture(String),
+
+    /// Font failed to be parsed.
+    Font(String),
 }
 
 impl From<String> for RenderError {
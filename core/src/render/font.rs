@@ -0,0 +1,233 @@
+//! Glyph atlas, which rasterizes glyphs of a TTF font, or cuts them out of a
+//! bitmap font, into a single image that's uploaded as a texture.
+
+use super::*;
+
+use image::RgbaImage;
+use rusttype::{point, Scale};
+use std::collections::HashMap;
+
+/// Size of the atlas TTF glyphs are packed into.
+const ATLAS_SIZE: u32 = 512;
+
+/// Glyph of a font, where distances are in pixels of the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    /// Distance the pen moves after the glyph.
+    pub advance: f32,
+
+    /// Rectangle covered by the glyph relative to the pen on the baseline,
+    /// where y goes down.
+    pub bounds: Aabr<f32>,
+
+    /// Texture coordinates of the glyph in the atlas.
+    pub uv: Aabr<f32>,
+}
+
+impl Glyph {
+    /// Whether there's nothing to draw, like for spaces.
+    pub fn is_blank(&self) -> bool {
+        let size = self.bounds.size();
+        size.w <= 0.0 || size.h <= 0.0
+    }
+}
+
+enum Source {
+    Ttf {
+        font: rusttype::Font<'static>,
+        scale: Scale,
+    },
+
+    /// Grid of `columns` cells, which hold consecutive characters.
+    Bitmap {
+        cell: Vec2<u32>,
+        first: char,
+        columns: u32,
+    },
+}
+
+pub struct GlyphAtlas {
+    source: Source,
+    image: RgbaImage,
+
+    /// Glyphs which have been looked up, where missing glyphs are `None`.
+    glyphs: HashMap<char, Option<Glyph>>,
+
+    /// Height of a line, and distance from the top of a line to the baseline.
+    line_height: f32,
+    ascent: f32,
+
+    /// Position of the next glyph, and height of the row it's in.
+    cursor: Vec2<u32>,
+    row_height: u32,
+
+    /// Whether the image changed since it was last uploaded.
+    dirty: bool,
+}
+
+impl GlyphAtlas {
+    /// Atlas which rasterizes glyphs of a TTF font as they're needed, with
+    /// lines `height` pixels tall.
+    pub fn ttf(bytes: &'static [u8], height: f32) -> Result<Self, RenderError> {
+        let font =
+            rusttype::Font::from_bytes(bytes).map_err(|err| RenderError::Font(err.to_string()))?;
+        let scale = Scale::uniform(height);
+        let metrics = font.v_metrics(scale);
+
+        let line_height = metrics.ascent - metrics.descent + metrics.line_gap;
+        let image = RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE);
+        let source = Source::Ttf { font, scale };
+        Ok(Self::new(source, image, line_height, metrics.ascent))
+    }
+
+    /// Atlas of a monospace bitmap font, where characters starting at `first`
+    /// are laid out in rows of `cell` sized glyphs.
+    pub fn bitmap(image: RgbaImage, cell: Vec2<u32>, first: char) -> Self {
+        let columns = image.width() / cell.x.max(1);
+        let source = Source::Bitmap {
+            cell,
+            first,
+            columns,
+        };
+        Self::new(source, image, cell.y as f32, cell.y as f32)
+    }
+
+    fn new(source: Source, image: RgbaImage, line_height: f32, ascent: f32) -> Self {
+        Self {
+            source,
+            image,
+            glyphs: HashMap::new(),
+            line_height,
+            ascent,
+            cursor: Vec2::zero(),
+            row_height: 0,
+            dirty: true,
+        }
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    pub fn ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    /// Whether the atlas should be sampled smoothly, which bitmap fonts aren't.
+    pub fn smooth(&self) -> bool {
+        match self.source {
+            Source::Ttf { .. } => true,
+            Source::Bitmap { .. } => false,
+        }
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Returns whether the image changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    /// Looks up a glyph, rasterizing it into the atlas the first time.
+    pub fn glyph(&mut self, c: char) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return *glyph;
+        }
+
+        let glyph = match self.source {
+            Source::Ttf { .. } => self.rasterize(c),
+            Source::Bitmap {
+                cell,
+                first,
+                columns,
+            } => {
+                let index = (c as u32).checked_sub(first as u32);
+                let rows = self.image.height() / cell.y.max(1);
+                index.filter(|i| *i < columns * rows).map(|i| {
+                    let min = Vec2::new(i % columns, i / columns) * cell;
+                    let size = cell.numcast().unwrap();
+                    Glyph {
+                        advance: size.x,
+                        bounds: Aabr {
+                            min: Vec2::new(0.0, -size.y),
+                            max: Vec2::new(size.x, 0.0),
+                        },
+                        uv: self.uv(min, cell),
+                    }
+                })
+            }
+        };
+
+        self.glyphs.insert(c, glyph);
+        glyph
+    }
+
+    /// Rasterizes a TTF glyph into the next free spot of the atlas, returning
+    /// `None` once the atlas is full.
+    fn rasterize(&mut self, c: char) -> Option<Glyph> {
+        let glyph = match &self.source {
+            Source::Ttf { font, scale } => font.glyph(c).scaled(*scale).positioned(point(0.0, 0.0)),
+            Source::Bitmap { .. } => return None,
+        };
+        let advance = glyph.unpositioned().h_metrics().advance_width;
+
+        let rect = match glyph.pixel_bounding_box() {
+            Some(rect) => rect,
+            // NOTE: glyphs like spaces have nothing to draw.
+            None => {
+                return Some(Glyph {
+                    advance,
+                    bounds: Aabr::new_empty(Vec2::zero()),
+                    uv: Aabr::new_empty(Vec2::zero()),
+                })
+            }
+        };
+        let size = Vec2::new(rect.width() as u32, rect.height() as u32);
+
+        // NOTE: glyphs are padded, so that filtering doesn't bleed neighbours in.
+        if self.cursor.x + size.x + 1 > self.image.width() {
+            self.cursor = Vec2::new(0, self.cursor.y + self.row_height + 1);
+            self.row_height = 0;
+        }
+        if self.cursor.y + size.y > self.image.height() {
+            return None;
+        }
+
+        let origin = self.cursor;
+        let image = &mut self.image;
+        glyph.draw(|x, y, coverage| {
+            let alpha = (coverage * 255.0).round() as u8;
+            image.put_pixel(
+                origin.x + x,
+                origin.y + y,
+                image::Rgba([255, 255, 255, alpha]),
+            );
+        });
+        self.cursor.x += size.x + 1;
+        self.row_height = self.row_height.max(size.y);
+        self.dirty = true;
+
+        Some(Glyph {
+            advance,
+            bounds: Aabr {
+                min: Vec2::new(rect.min.x, rect.min.y).numcast().unwrap(),
+                max: Vec2::new(rect.max.x, rect.max.y).numcast().unwrap(),
+            },
+            uv: self.uv(origin, size),
+        })
+    }
+
+    /// Texture coordinates of a rectangle of the atlas in pixels.
+    fn uv(&self, min: Vec2<u32>, size: Vec2<u32>) -> Aabr<f32> {
+        let (width, height) = self.image.dimensions();
+        let atlas = Vec2::new(width as f32, height as f32);
+        let min: Vec2<f32> = min.numcast().unwrap();
+        let size: Vec2<f32> = size.numcast().unwrap();
+        Aabr {
+            min: min / atlas,
+            max: (min + size) / atlas,
+        }
+    }
+}
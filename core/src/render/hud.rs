@@ -0,0 +1,35 @@
+//! Immediate-mode overlay, which systems fill with text and sprites every
+//! frame, for debugging tools and user interfaces which don't need entities
+//! of their own.
+
+use super::*;
+
+/// Resource of sprites and text drawn on top of the user interface, in logical
+/// pixels from the top left of the screen, which is cleared after every frame.
+///
+/// Sprites are drawn in the order they're pushed, and text over all of them.
+#[derive(Default)]
+pub struct Hud {
+    pub(crate) texts: Vec<(Vec2<f32>, Text, Rgba<f32>)>,
+    pub(crate) sprites: Vec<(Aabr<f32>, u32, Rgba<f32>)>,
+}
+
+impl Hud {
+    pub fn text(&mut self, position: Vec2<f32>, text: Text, color: Rgba<f32>) {
+        self.texts.push((position, text, color));
+    }
+
+    /// Draws a layer of the texture array stretched over `bounds`.
+    pub fn sprite(&mut self, bounds: Aabr<f32>, texture: u32, color: Rgba<f32>) {
+        self.sprites.push((bounds, texture, color));
+    }
+
+    pub fn rect(&mut self, bounds: Aabr<f32>, color: Rgba<f32>) {
+        self.sprite(bounds, WHITE, color);
+    }
+
+    pub fn clear(&mut self) {
+        self.texts.clear();
+        self.sprites.clear();
+    }
+}
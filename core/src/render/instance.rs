@@ -0,0 +1,265 @@
+//! CPU side of the instanced sprite pipeline.
+//!
+//! Instances are stored densely so they can be uploaded to a single buffer and
+//! drawn with one call, while being addressed by a stable key (the entity id).
+
+use super::memory::Pod;
+
+use specs::prelude::*;
+use std::cmp::Ordering;
+use std::ops::Range;
+use vek::*;
+
+/// Per-instance attributes of a sprite.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+#[repr(C, packed)]
+pub struct Instance {
+    /// Position of the center of the sprite, where sprites with a greater z
+    /// are drawn over the others within the same layer.
+    pub pos: [f32; 3],
+
+    /// Size of the sprite in tiles.
+    pub size: [f32; 2],
+
+    /// Texture array index.
+    pub idx: u32,
+
+    /// Color the texture is multiplied by.
+    pub color: [f32; 4],
+
+    /// Quarter turns the texture is rotated by, counter-clockwise.
+    pub rotation: u32,
+}
+
+unsafe impl Pod for Instance {}
+
+impl Instance {
+    pub fn new(pos: Vec3<f32>, size: Vec2<f32>, idx: u32, color: Rgba<f32>) -> Self {
+        Self {
+            pos: pos.into_array(),
+            size: size.into_array(),
+            idx,
+            color: color.into_array(),
+            rotation: 0,
+        }
+    }
+
+    pub fn rotated(mut self, quarter_turns: u32) -> Self {
+        self.rotation = quarter_turns % 4;
+        self
+    }
+
+    /// A single tile sprite at a tile coordinate.
+    pub fn tile(coordinate: Vec2<i32>, idx: u32, color: Rgba<f32>) -> Self {
+        let pos: Vec2<f32> = coordinate.numcast().unwrap();
+        Self::new(pos.into(), Vec2::one(), idx, color)
+    }
+
+    /// Whether the sprite has to be blended with what's behind it.
+    pub fn is_translucent(&self) -> bool {
+        self.color[3] < 1.0
+    }
+
+    fn depth(&self) -> f32 {
+        self.pos[2]
+    }
+}
+
+/// Dense array of instances addressed by key, which keeps track of the slots
+/// that changed since they were last uploaded.
+#[derive(Default)]
+pub struct Instances {
+    data: Vec<Instance>,
+
+    /// Slot of every key, indexed by key.
+    slots: Vec<Option<u32>>,
+
+    /// Key of every slot, indexed by slot.
+    keys: Vec<u32>,
+
+    /// Slots which have to be uploaded again.
+    dirty: BitSet,
+}
+
+impl Instances {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[Instance] {
+        &self.data
+    }
+
+    pub fn get(&self, key: u32) -> Option<&Instance> {
+        let slot = (*self.slots.get(key as usize)?)?;
+        self.data.get(slot as usize)
+    }
+
+    /// Inserts or replaces the instance of a key.
+    pub fn insert(&mut self, key: u32, instance: Instance) {
+        if self.slots.len() <= key as usize {
+            self.slots.resize(key as usize + 1, None);
+        }
+
+        let slot = match self.slots[key as usize] {
+            Some(slot) => {
+                self.data[slot as usize] = instance;
+                slot
+            }
+            None => {
+                let slot = self.data.len() as u32;
+                self.data.push(instance);
+                self.keys.push(key);
+                self.slots[key as usize] = Some(slot);
+                slot
+            }
+        };
+
+        self.dirty.add(slot);
+    }
+
+    /// Removes the instance of a key, by moving the last instance into it's slot.
+    pub fn remove(&mut self, key: u32) -> Option<Instance> {
+        let slot = self.slots.get_mut(key as usize)?.take()?;
+        let instance = self.data.swap_remove(slot as usize);
+        self.keys.swap_remove(slot as usize);
+
+        self.dirty.remove(self.data.len() as u32);
+        if let Some(&moved) = self.keys.get(slot as usize) {
+            self.slots[moved as usize] = Some(slot);
+            self.dirty.add(slot);
+        }
+
+        Some(instance)
+    }
+
+    /// Sorts instances back to front, so they can be drawn without depth testing.
+    // NOTE: depths come from gameplay, so NaN is ordered instead of panicking,
+    // and only the sprites with it are drawn out of order.
+    pub fn sort(&mut self) {
+        let data = &self.data;
+        let is_sorted = data
+            .windows(2)
+            .all(|w| w[0].depth().total_cmp(&w[1].depth()) != Ordering::Greater);
+        if is_sorted {
+            return;
+        }
+
+        let mut order: Vec<usize> = (0..data.len()).collect();
+        order.sort_by(|&a, &b| data[a].depth().total_cmp(&data[b].depth()));
+
+        self.data = order.iter().map(|&slot| self.data[slot]).collect();
+        self.keys = order.iter().map(|&slot| self.keys[slot]).collect();
+        for (slot, &key) in self.keys.iter().enumerate() {
+            self.slots[key as usize] = Some(slot as u32);
+        }
+
+        self.invalidate();
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.slots.clear();
+        self.keys.clear();
+        self.dirty.clear();
+    }
+
+    /// Marks every slot as changed, for when the whole buffer has to be uploaded.
+    pub fn invalidate(&mut self) {
+        for slot in 0..self.data.len() {
+            self.dirty.add(slot as u32);
+        }
+    }
+
+    /// Takes the ranges of consecutive changed slots, so that they can be
+    /// uploaded with as few calls as possible.
+    pub fn take_dirty(&mut self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for slot in (&self.dirty).join() {
+            let slot = slot as usize;
+            match ranges.last_mut() {
+                Some(range) if range.end == slot => range.end += 1,
+                _ => ranges.push(slot..slot + 1),
+            }
+        }
+        self.dirty.clear();
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(x: i32) -> Instance {
+        Instance::tile(Vec2::new(x, 0), 0, Rgba::white())
+    }
+
+    #[test]
+    fn dirty_ranges_are_merged() {
+        let mut instances = Instances::default();
+        for key in 0..10 {
+            instances.insert(key, instance(key as i32));
+        }
+        assert_eq!(instances.take_dirty(), vec![0..10]);
+        assert!(instances.take_dirty().is_empty());
+
+        instances.insert(2, instance(20));
+        instances.insert(3, instance(30));
+        instances.insert(7, instance(70));
+        assert_eq!(instances.take_dirty(), vec![2..4, 7..8]);
+    }
+
+    #[test]
+    fn sort_keeps_keys() {
+        let mut instances = Instances::default();
+        for (key, z) in [3.0, 1.0, 2.0].iter().enumerate() {
+            let mut instance = instance(key as i32);
+            instance.pos[2] = *z;
+            instances.insert(key as u32, instance);
+        }
+        instances.take_dirty();
+
+        instances.sort();
+        let depths: Vec<f32> = instances.as_slice().iter().map(Instance::depth).collect();
+        assert_eq!(depths, vec![1.0, 2.0, 3.0]);
+        assert_eq!(instances.get(0).unwrap().depth(), 3.0);
+        assert_eq!(instances.take_dirty(), vec![0..3]);
+
+        instances.sort();
+        assert!(instances.take_dirty().is_empty());
+
+        let mut broken = instance(3);
+        broken.pos[2] = -f32::NAN;
+        instances.insert(3, broken);
+        instances.sort();
+        assert!(instances.as_slice()[0].depth().is_nan());
+        assert_eq!(instances.get(0).unwrap().depth(), 3.0);
+        instances.take_dirty();
+        instances.sort();
+        assert!(instances.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn remove_moves_last_instance() {
+        let mut instances = Instances::default();
+        for key in 0..4 {
+            instances.insert(key, instance(key as i32));
+        }
+        instances.take_dirty();
+
+        assert_eq!(instances.remove(1), Some(instance(1)));
+        assert_eq!(instances.len(), 3);
+        assert_eq!(instances.get(3), Some(&instance(3)));
+        assert_eq!(instances.get(1), None);
+        assert_eq!(instances.take_dirty(), vec![1..2]);
+
+        assert_eq!(instances.remove(3), Some(instance(3)));
+        assert_eq!(instances.remove(3), None);
+        assert_eq!(instances.take_dirty(), vec![1..2]);
+    }
+}
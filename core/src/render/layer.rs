@@ -0,0 +1,312 @@
+//! Render layers, which are drawn on top of each other in a fixed order, each
+//! with it's own render state.
+
+use super::*;
+
+use glow::*;
+
+/// Layer of the scene a sprite is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Layer {
+    Floor,
+    Blocks,
+    Units,
+    Effects,
+    Overlay,
+    Ui,
+}
+
+impl Layer {
+    /// Every layer, in the order they are drawn.
+    pub const ALL: [Layer; 6] = [
+        Layer::Floor,
+        Layer::Blocks,
+        Layer::Units,
+        Layer::Effects,
+        Layer::Overlay,
+        Layer::Ui,
+    ];
+
+    /// Render state used to draw the layer.
+    pub fn state(self) -> LayerState {
+        use Layer::*;
+        match self {
+            Floor | Blocks | Units => LayerState {
+                space: Space::World,
+                sorting: Sorting::Depth,
+            },
+            Effects | Overlay => LayerState {
+                space: Space::World,
+                sorting: Sorting::Painter,
+            },
+            Ui => LayerState {
+                space: Space::Screen,
+                sorting: Sorting::Painter,
+            },
+        }
+    }
+
+    /// Blend mode a layer uses, unless configured otherwise.
+    pub fn default_blend(self) -> Blend {
+        use Layer::*;
+        match self {
+            Floor => Blend::Opaque,
+            Blocks | Units | Overlay | Ui => Blend::Alpha,
+            Effects => Blend::Additive,
+        }
+    }
+}
+
+/// How the colors of sprites are combined with what's already been drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Blend {
+    /// Colors replace what's behind them.
+    Opaque,
+
+    /// Colors are mixed with what's behind them by their alpha.
+    Alpha,
+
+    /// Colors are scaled by their alpha and added to what's behind them,
+    /// which brightens the scene for glowing effects.
+    Additive,
+
+    /// Like alpha blending, but colors are multiplied by their alpha in the
+    /// shader, which blends correctly with filtered textures.
+    Premultiplied,
+}
+
+impl Blend {
+    /// Whether the shader has to multiply colors by their alpha.
+    pub fn premultiply(self) -> bool {
+        self == Blend::Premultiplied
+    }
+
+    pub(crate) unsafe fn apply(self, gl: &Context) {
+        let (src, dst) = match self {
+            Blend::Opaque => return gl.disable(glow::BLEND),
+            Blend::Alpha => (glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA),
+            Blend::Additive => (glow::SRC_ALPHA, glow::ONE),
+            Blend::Premultiplied => (glow::ONE, glow::ONE_MINUS_SRC_ALPHA),
+        };
+
+        gl.enable(glow::BLEND);
+        gl.blend_equation(glow::FUNC_ADD);
+        gl.blend_func_separate(src, dst, glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+    }
+}
+
+/// Resource for configuring the blend mode of every layer.
+pub struct BlendModes([Blend; 6]);
+
+impl Default for BlendModes {
+    fn default() -> Self {
+        let mut modes = [Blend::Opaque; 6];
+        for layer in Layer::ALL.iter() {
+            modes[*layer as usize] = layer.default_blend();
+        }
+        BlendModes(modes)
+    }
+}
+
+impl BlendModes {
+    pub fn get(&self, layer: Layer) -> Blend {
+        self.0[layer as usize]
+    }
+
+    pub fn set(&mut self, layer: Layer, blend: Blend) {
+        self.0[layer as usize] = blend;
+    }
+}
+
+/// Coordinate space the positions of a layer are in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Space {
+    /// Positions are in tiles, and move with the camera.
+    World,
+
+    /// Positions are in logical pixels from the top left of the screen, which
+    /// are scaled by the DPI of the screen.
+    Screen,
+}
+
+/// How sprites within a layer are ordered by their z position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sorting {
+    /// Opaque sprites are depth tested, and translucent sprites are sorted
+    /// back to front and drawn after them, without writing depth.
+    Depth,
+
+    /// Sprites are sorted back to front, and drawn in that order.
+    Painter,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LayerState {
+    pub space: Space,
+    pub sorting: Sorting,
+}
+
+impl LayerState {
+    /// Applies the state to the context, before the layer is drawn.
+    pub unsafe fn apply(&self, gl: &Context) {
+        match self.sorting {
+            Sorting::Depth => {
+                // NOTE: depth is only compared within a layer, because layers
+                // are always drawn on top of the previous ones.
+                gl.enable(glow::DEPTH_TEST);
+                gl.depth_func(glow::LEQUAL);
+                gl.clear(glow::DEPTH_BUFFER_BIT);
+            }
+            Sorting::Painter => gl.disable(glow::DEPTH_TEST),
+        }
+    }
+}
+
+/// Sprites of a single layer, where tiles are cached by chunk.
+pub struct LayerBatch {
+    pub state: LayerState,
+    chunks: Chunks,
+    sprites: SpriteBatch,
+
+    /// Translucent sprites of depth tested layers, which are always sorted.
+    translucent: SpriteBatch,
+}
+
+impl LayerBatch {
+    pub fn new(gl: &Context, quad: &Buffer<Vertex>, layer: Layer) -> Result<Self, RenderError> {
+        let state = layer.state();
+        let sorted = state.sorting == Sorting::Painter;
+
+        let mut sprites = SpriteBatch::new(gl, quad, 1024)?;
+        sprites.sorted = sorted;
+
+        let mut translucent = SpriteBatch::new(gl, quad, 64)?;
+        translucent.sorted = true;
+
+        Ok(Self {
+            state,
+            chunks: Chunks::new(sorted),
+            sprites,
+            translucent,
+        })
+    }
+
+    /// Removes the sprite of an entity from wherever it is in the layer.
+    fn remove(&mut self, id: u32) {
+        self.chunks.remove(id);
+        self.sprites.instances.remove(id);
+        self.translucent.instances.remove(id);
+    }
+
+    /// Draws the sprites of the layer, and the chunks which overlap with the
+    /// `bounds` of the layer's space.
+    pub unsafe fn draw(&self, gl: &Context, bounds: Aabr<f32>) {
+        self.chunks.draw(gl, bounds);
+        self.sprites.draw(gl);
+
+        if !self.translucent.instances.is_empty() {
+            gl.depth_mask(false);
+            self.translucent.draw(gl);
+            gl.depth_mask(true);
+        }
+    }
+}
+
+/// Sprites of every layer, which keeps track of the layer each entity is in.
+pub struct Layers {
+    batches: Vec<(Layer, LayerBatch)>,
+
+    /// Layer of every sprite, indexed by entity id.
+    locations: Vec<Option<Layer>>,
+}
+
+impl Layers {
+    pub fn new(gl: &Context, quad: &Buffer<Vertex>) -> Result<Self, RenderError> {
+        let mut batches = Vec::new();
+        for layer in Layer::ALL.iter() {
+            batches.push((*layer, LayerBatch::new(gl, quad, *layer)?));
+        }
+
+        Ok(Self {
+            batches,
+            locations: Vec::new(),
+        })
+    }
+
+    /// Inserts or replaces the sprite of an entity, where sprites with a `chunk`
+    /// coordinate are static tiles.
+    pub fn insert(
+        &mut self,
+        gl: &Context,
+        quad: &Buffer<Vertex>,
+        id: u32,
+        layer: Layer,
+        chunk: Option<Vec2<i32>>,
+        instance: Instance,
+    ) -> Result<(), RenderError> {
+        if self.locations.len() <= id as usize {
+            self.locations.resize(id as usize + 1, None);
+        }
+
+        if let Some(previous) = self.locations[id as usize] {
+            if previous != layer {
+                self.remove(id);
+            }
+        }
+        self.locations[id as usize] = Some(layer);
+
+        let (_, batch) = &mut self.batches[layer as usize];
+        let translucent = batch.state.sorting == Sorting::Depth && instance.is_translucent();
+        let LayerBatch {
+            chunks,
+            sprites,
+            translucent: translucent_sprites,
+            ..
+        } = batch;
+
+        // NOTE: the sprite is only removed from the batches it isn't going
+        // into, so that updating a sprite in place only changes it's own slot.
+        match chunk {
+            _ if translucent => {
+                chunks.remove(id);
+                sprites.instances.remove(id);
+                translucent_sprites.instances.insert(id, instance);
+            }
+            Some(coordinate) => {
+                sprites.instances.remove(id);
+                translucent_sprites.instances.remove(id);
+                chunks.insert(gl, quad, id, coordinate, instance)?;
+            }
+            None => {
+                chunks.remove(id);
+                translucent_sprites.instances.remove(id);
+                sprites.instances.insert(id, instance);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        let layer = self.locations.get_mut(id as usize).and_then(Option::take);
+        if let Some(layer) = layer {
+            let (_, batch) = &mut self.batches[layer as usize];
+            batch.remove(id);
+        }
+    }
+
+    /// Uploads the sprites which changed in every layer.
+    pub fn sync(&mut self, gl: &Context, quad: &Buffer<Vertex>) -> Result<(), RenderError> {
+        for (_, batch) in self.batches.iter_mut() {
+            batch.chunks.sync(gl, quad)?;
+            batch.sprites.sync(gl, quad)?;
+            batch.translucent.sync(gl, quad)?;
+        }
+        Ok(())
+    }
+
+    /// Iterates over the layers in the order they have to be drawn.
+    pub fn iter(&self) -> impl Iterator<Item = &(Layer, LayerBatch)> {
+        self.batches.iter()
+    }
+}
@@ -0,0 +1,259 @@
+//! Debug lines and shapes, which any system can draw to visualize what it's
+//! doing; they are compiled out of release builds, where drawing does nothing.
+
+use super::*;
+
+#[cfg(debug_assertions)]
+use glow::*;
+
+/// Number of segments circles are drawn with.
+#[cfg(debug_assertions)]
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// A line which is drawn until it's lifetime runs out.
+#[cfg(debug_assertions)]
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    space: Space,
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    color: Rgba<f32>,
+
+    /// Seconds left to draw the segment for.
+    lifetime: f32,
+}
+
+/// Resource for drawing debug shapes, in tiles or in logical pixels from the top
+/// left of the screen, for `lifetime` seconds, where shapes with a lifetime
+/// of zero are only drawn in the next frame.
+#[derive(Default)]
+pub struct DebugDraw {
+    #[cfg(debug_assertions)]
+    segments: Vec<Segment>,
+}
+
+#[cfg_attr(not(debug_assertions), allow(unused_variables))]
+impl DebugDraw {
+    pub fn line(
+        &mut self,
+        space: Space,
+        a: Vec2<f32>,
+        b: Vec2<f32>,
+        color: Rgba<f32>,
+        lifetime: f32,
+    ) {
+        #[cfg(debug_assertions)]
+        self.segments.push(Segment {
+            space,
+            a,
+            b,
+            color,
+            lifetime,
+        });
+    }
+
+    pub fn rect(&mut self, space: Space, bounds: Aabr<f32>, color: Rgba<f32>, lifetime: f32) {
+        let Aabr { min, max } = bounds;
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+        for i in 0..4 {
+            self.line(space, corners[i], corners[(i + 1) % 4], color, lifetime);
+        }
+    }
+
+    pub fn circle(
+        &mut self,
+        space: Space,
+        center: Vec2<f32>,
+        radius: f32,
+        color: Rgba<f32>,
+        lifetime: f32,
+    ) {
+        #[cfg(debug_assertions)]
+        {
+            let point = |i: usize| {
+                let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+                center + Vec2::new(angle.cos(), angle.sin()) * radius
+            };
+            for i in 0..CIRCLE_SEGMENTS {
+                self.line(space, point(i), point(i + 1), color, lifetime);
+            }
+        }
+    }
+
+    /// Draws a line with a head pointing at `to`.
+    pub fn arrow(
+        &mut self,
+        space: Space,
+        from: Vec2<f32>,
+        to: Vec2<f32>,
+        color: Rgba<f32>,
+        lifetime: f32,
+    ) {
+        #[cfg(debug_assertions)]
+        {
+            self.line(space, from, to, color, lifetime);
+
+            let length = from.distance(to);
+            if length > 0.0 {
+                let back = (from - to) / length * (length * 0.25);
+                let side = Vec2::new(-back.y, back.x) * 0.5;
+                self.line(space, to, to + back + side, color, lifetime);
+                self.line(space, to, to + back - side, color, lifetime);
+            }
+        }
+    }
+
+    /// Takes the segments to draw this frame, and forgets the ones which have
+    /// been drawn for longer than their lifetime after `elapsed` seconds.
+    #[cfg(debug_assertions)]
+    fn flush(&mut self, elapsed: f32) -> Vec<Segment> {
+        let segments = self.segments.clone();
+        self.segments.retain(|segment| segment.lifetime > elapsed);
+        for segment in self.segments.iter_mut() {
+            segment.lifetime -= elapsed;
+        }
+        segments
+    }
+}
+
+#[cfg(debug_assertions)]
+#[derive(Copy, Clone, Default, Debug)]
+#[repr(C, packed)]
+pub struct LineVertex {
+    pos: [f32; 2],
+    color: [f32; 4],
+}
+
+#[cfg(debug_assertions)]
+unsafe impl Pod for LineVertex {}
+
+/// Pipeline which draws the debug lines of every frame.
+#[cfg(debug_assertions)]
+pub struct LineBatch {
+    program: Program,
+
+    /// Number of vertices in world-space, which come before screen-space ones.
+    world: usize,
+    vertices: Vec<LineVertex>,
+
+    buffer: Buffer<LineVertex>,
+    vertex_array: Option<VertexArrayId>,
+}
+
+#[cfg(debug_assertions)]
+impl LineBatch {
+    pub fn new(gl: &Context) -> Result<Self, RenderError> {
+        let program = Program::new(
+            gl,
+            include_str!("shaders/line_vss.glsl"),
+            include_str!("shaders/line_fss.glsl"),
+            &[(loc::VERT_POS, "vert_pos"), (loc::INST_COL, "inst_col")],
+        )?;
+
+        let buffer = Buffer::dynamic(gl, glow::ARRAY_BUFFER, 1024)?;
+        let vertex_array = Some(unsafe { gl.create_vertex_array()? });
+        unsafe { Self::bind_attributes(gl, vertex_array, &buffer) };
+
+        Ok(Self {
+            program,
+            world: 0,
+            vertices: Vec::new(),
+            buffer,
+            vertex_array,
+        })
+    }
+
+    #[rustfmt::skip]
+    unsafe fn bind_attributes(
+        gl: &Context,
+        vertex_array: Option<VertexArrayId>,
+        buffer: &Buffer<LineVertex>,
+    ) {
+        gl.bind_vertex_array(vertex_array);
+        attributes(gl, buffer, 0, &[
+            (loc::VERT_POS, 2, glow::FLOAT, offset_of!(LineVertex, pos)),
+            (loc::INST_COL, 4, glow::FLOAT, offset_of!(LineVertex, color)),
+        ]);
+        gl.bind_vertex_array(None);
+    }
+
+    /// Takes the lines to draw from the resource, and uploads them.
+    pub fn sync(
+        &mut self,
+        gl: &Context,
+        draw: &mut DebugDraw,
+        elapsed: f32,
+    ) -> Result<(), RenderError> {
+        let mut segments = draw.flush(elapsed);
+        segments.sort_by_key(|segment| segment.space == Space::Screen);
+
+        self.vertices.clear();
+        self.world = 0;
+        for segment in segments {
+            if segment.space == Space::World {
+                self.world += 2;
+            }
+            for point in [segment.a, segment.b].iter() {
+                self.vertices.push(LineVertex {
+                    pos: point.into_array(),
+                    color: segment.color.into_array(),
+                });
+            }
+        }
+
+        if self.vertices.len() > self.buffer.size() {
+            self.buffer._delete(gl);
+            let size = self.vertices.len().next_power_of_two();
+            self.buffer = Buffer::dynamic(gl, glow::ARRAY_BUFFER, size)?;
+            unsafe { Self::bind_attributes(gl, self.vertex_array, &self.buffer) };
+        }
+
+        unsafe {
+            // SAFETY: the vertices aren't mutated until they are drawn.
+            self.buffer.update(gl, 0, &self.vertices);
+        }
+        Ok(())
+    }
+
+    /// Draws the lines over everything, with their world and screen transforms.
+    pub unsafe fn draw(&self, gl: &Context, world: Mat4<f32>, screen: Mat4<f32>) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        gl.disable(glow::DEPTH_TEST);
+        Blend::Alpha.apply(gl);
+        self.program.use_program(gl);
+        gl.bind_vertex_array(self.vertex_array);
+
+        let ranges = [
+            (world, 0, self.world),
+            (screen, self.world, self.vertices.len()),
+        ];
+        for &(transform, start, end) in ranges.iter() {
+            if start < end {
+                self.program.set_uniform(gl, "transform", transform);
+                gl.draw_arrays(glow::LINES, start as i32, (end - start) as i32);
+            }
+        }
+
+        gl.bind_vertex_array(None);
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifetime_test() {
+        let mut draw = DebugDraw::default();
+        let color = Rgba::white();
+        draw.line(Space::World, Vec2::zero(), Vec2::one(), color, 0.0);
+        draw.rect(Space::Screen, Aabr::new_empty(Vec2::zero()), color, 1.0);
+
+        assert_eq!(draw.flush(0.5).len(), 5);
+        assert_eq!(draw.flush(0.5).len(), 4);
+        assert_eq!(draw.flush(0.5).len(), 0);
+    }
+}
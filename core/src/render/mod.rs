@@ -4,38 +4,120 @@
 //! on Web and Native targets, so we're targetting mostly OpenGL ES 3.0 features.
 
 mod buffer;
+mod capture;
+mod chunk;
 mod error;
+mod font;
+mod hud;
+mod instance;
+mod layer;
+mod lines;
 mod memory;
+mod post;
 mod program;
+mod sprite;
+mod target;
+mod text;
 mod texture;
 mod types;
 mod uniform;
 
 pub use buffer::*;
+pub use capture::*;
+pub use chunk::*;
 pub use error::*;
+pub use font::*;
+pub use hud::*;
+pub use instance::*;
+pub use layer::*;
+pub use lines::*;
+pub use post::*;
 pub use program::*;
+pub use sprite::*;
+pub use target::*;
+pub use text::*;
 pub use texture::*;
 pub use types::*;
 pub use uniform::*;
 
 use crate::components::*;
+use crate::construction::ConstructionSite;
+use crate::conveyor::Conveyor;
 use crate::game::Scene;
+use crate::health::Health;
+use crate::items::Items;
 use crate::loader::Loader;
-use crate::state::GameStart;
+use crate::profiler::{Category, Profiler};
+use crate::settings::{Filtering, Settings};
+use crate::state::{DeltaTime, GameStart};
 use memory::Pod;
 
 use glow::*;
-use instant::{Duration, Instant};
+use instant::Instant;
 use specs::prelude::*;
 use std::convert::TryInto;
 use vek::*;
 
+/// Layers of the texture array, which has room for every bundled image.
+const TEXTURE_LAYERS: u32 = 32;
+
+/// Layer of the texture array which is solid white, for untextured sprites.
+pub const WHITE: u32 = TEXTURE_LAYERS - 1;
+
+/// Color of the progress bars of construction sites.
+const CONSTRUCTION: Rgba<f32> = Rgba {
+    r: 0.95,
+    g: 0.75,
+    b: 0.2,
+    a: 1.0,
+};
+
+/// Colors of the health bars of damaged blocks, when they're nearly destroyed
+/// and when they're nearly intact.
+const HEALTH_LOW: Rgba<f32> = Rgba {
+    r: 0.9,
+    g: 0.2,
+    b: 0.15,
+    a: 1.0,
+};
+const HEALTH_HIGH: Rgba<f32> = Rgba {
+    r: 0.3,
+    g: 0.85,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Background and fill of a bar along the bottom of a block, which is filled
+/// from the left by `value`, from 0 to 1.
+fn progress_bar(center: Vec2<f32>, size: Vec2<f32>, value: f32, color: Rgba<f32>) -> [Instance; 2] {
+    let width = size.x * 0.8;
+    let bottom = center.y - size.y / 2.0 + 0.15;
+    let background = Instance::new(
+        Vec3::new(center.x, bottom, 0.0),
+        Vec2::new(width, 0.12),
+        WHITE,
+        Rgba::new(0.0, 0.0, 0.0, 0.6),
+    );
+    let fill = width * value.clamp(0.0, 1.0);
+    let left = center.x - width / 2.0;
+    let bar = Instance::new(
+        Vec3::new(left + fill / 2.0, bottom, 0.0),
+        Vec2::new(fill, 0.08),
+        WHITE,
+        color,
+    );
+    [background, bar]
+}
+
 /// Constant vertex attribute locations used across shaders.
 mod loc {
     pub const VERT_POS: u32 = 0;
     pub const TEXT_POS: u32 = 1;
     pub const TEXT_IDX: u32 = 2;
-    pub const VERT_COL: u32 = 3;
+    pub const INST_COL: u32 = 3;
+    pub const INST_POS: u32 = 4;
+    pub const INST_SIZE: u32 = 5;
+    pub const INST_ROT: u32 = 6;
 }
 
 #[derive(Copy, Clone, Default)]
@@ -46,12 +128,6 @@ pub struct Vertex {
 
     /// Texture coordinates.
     tex: [f32; 2],
-
-    /// Texture array index.
-    idx: u32,
-
-    /// Color of this vertex.
-    color: [f32; 4],
 }
 
 unsafe impl Pod for Vertex {}
@@ -60,6 +136,9 @@ pub trait Pipeline {
     /// A vertex should be capable of casting itself to buffer data, and configuring
     /// the vertex attribute pointer, by knowing it's own memory layout.
     type Vertex: Copy + Pod;
+
+    /// Data which is repeated for every vertex of an instance.
+    type Instance: Copy + Pod;
 }
 
 pub struct Quad<P: Pipeline> {
@@ -76,15 +155,15 @@ impl<P: Pipeline> Quad<P> {
 }
 
 impl Quad<SpritePipeline> {
+    /// Quad of size one centered on the origin, which is scaled, rotated and
+    /// moved into place by every sprite instance.
     #[rustfmt::skip]
-    pub fn rect(xy: Vec2<f32>, s: f32, idx: u32, color: Rgba<f32>) -> Self {
-        let [x, y] = xy.into_array();
-        let color = color.into_array();
+    pub fn unit() -> Self {
         Self::new(
-            Vertex { pos: [ 0.5 + x + s,  0.5 + y + s], tex: [1.0, 1.0], idx, color },
-            Vertex { pos: [ 0.5 + x + s, -0.5 + y    ], tex: [1.0, 0.0], idx, color },
-            Vertex { pos: [-0.5 + x    ,  0.5 + y + s], tex: [0.0, 1.0], idx, color },
-            Vertex { pos: [-0.5 + x    , -0.5 + y    ], tex: [0.0, 0.0], idx, color },
+            Vertex { pos: [ 0.5,  0.5], tex: [1.0, 1.0] },
+            Vertex { pos: [ 0.5, -0.5], tex: [1.0, 0.0] },
+            Vertex { pos: [-0.5,  0.5], tex: [0.0, 1.0] },
+            Vertex { pos: [-0.5, -0.5], tex: [0.0, 0.0] },
         )
     }
 }
@@ -95,11 +174,6 @@ pub struct Mesh<P: Pipeline> {
 }
 
 impl<P: Pipeline> Mesh<P> {
-    /// Clear vertices to reuse allocated memory.
-    pub fn clear(&mut self) {
-        self.data.clear();
-    }
-
     /// Push a quad at the end of the mesh.
     pub fn push_quad(&mut self, quad: Quad<P>) {
         let Quad { a, b, c, d } = quad;
@@ -108,9 +182,10 @@ impl<P: Pipeline> Mesh<P> {
 }
 
 #[derive(Default)]
-struct SpritePipeline;
+pub struct SpritePipeline;
 impl Pipeline for SpritePipeline {
     type Vertex = Vertex;
+    type Instance = Instance;
 }
 
 /// Type which holds onto the OpenGL context, and the various objects that surrounds it.
@@ -118,20 +193,63 @@ pub struct Renderer {
     gl: Context,
 
     texture: Texture,
-    vertex_array: Option<VertexArrayId>,
     program: Program,
-    vertex_buffer: Buffer<SpritePipeline>,
 
-    grid_size: Vec2<usize>,
-    tile_mesh: Mesh<SpritePipeline>,
+    /// Whether the texture array is filtered linearly, as last set by the settings.
+    smooth: bool,
+
+    /// Unit quad shared by every sprite instance.
+    quad: Buffer<Vertex>,
+
+    /// Sprites of entities by layer, keyed by entity id.
+    layers: Layers,
+
+    /// Highlight of the tile under the cursor.
+    cursor: SpriteBatch,
+
+    /// Items on belts, which move every frame, so they're rebuilt every frame.
+    belt_items: SpriteBatch,
+
+    /// Progress bars over blocks, which are rebuilt every frame.
+    bars: SpriteBatch,
 
-    frame_duration: Vec<Duration>,
+    /// Rectangles of the overlay, which are rebuilt every frame.
+    hud: SpriteBatch,
+
+    text: TextBatch,
+    post: PostProcessor,
+
+    #[cfg(debug_assertions)]
+    lines: LineBatch,
+
+    coordinate_reader: Option<ReaderId<ComponentEvent>>,
+    color_reader: Option<ReaderId<ComponentEvent>>,
+    texture_reader: Option<ReaderId<ComponentEvent>>,
+    position_reader: Option<ReaderId<ComponentEvent>>,
+    size_reader: Option<ReaderId<ComponentEvent>>,
+    tile_reader: Option<ReaderId<ComponentEvent>>,
+    layer_reader: Option<ReaderId<ComponentEvent>>,
+    rotation_reader: Option<ReaderId<ComponentEvent>>,
+    footprint_reader: Option<ReaderId<ComponentEvent>>,
+
+    /// Entities of which the sprite has to be rebuilt.
+    changed: BitSet,
+    removed: BitSet,
 }
 
 impl Renderer {
-    pub fn new(gl: Context) -> Result<Self, RenderError> {
-        let (grid_height, grid_width) = (6, 6);
+    /// Reads back the last frame drawn to the screen, encoded as PNG.
+    pub fn screenshot(&self, size: Vec2<u32>) -> Result<Vec<u8>, RenderError> {
+        encode_png(read_pixels(&self.gl, size))
+    }
+
+    /// Replaces the font text is drawn with.
+    pub fn set_font(&mut self, atlas: GlyphAtlas) -> Result<(), RenderError> {
+        self.text = TextBatch::new(&self.gl, atlas)?;
+        Ok(())
+    }
 
+    pub fn new(gl: Context) -> Result<Self, RenderError> {
         let program = Program::new(
             &gl,
             include_str!("shaders/vss.glsl"),
@@ -140,93 +258,125 @@ impl Renderer {
                 (loc::VERT_POS, "vert_pos"),
                 (loc::TEXT_POS, "text_pos"),
                 (loc::TEXT_IDX, "text_idx"),
+                (loc::INST_COL, "inst_col"),
+                (loc::INST_POS, "inst_pos"),
+                (loc::INST_SIZE, "inst_size"),
+                (loc::INST_ROT, "inst_rot"),
             ],
         )?;
 
-        let texture = Texture::new(&gl, Vec3::new(32, 32, 6))?;
-
-        let tile_mesh = Mesh::default();
-
-        let buffer_size = 6 * 4 * 1000 * 321; // FIXME: buffer size shouldn't be hardcoded
-        let vertex_buffer = Buffer::dynamic(&gl, glow::ARRAY_BUFFER, buffer_size)?;
-
-        let vertex_array;
-        unsafe {
-            vertex_array = Some(gl.create_vertex_array()?);
-            gl.bind_vertex_array(vertex_array);
-
-            vertex_buffer.bind(&gl);
-
-            // TODO: refactor this mess
-            gl.vertex_attrib_pointer_f32(
-                loc::VERT_POS,
-                2,
-                glow::FLOAT,
-                false,
-                std::mem::size_of::<Vertex>().try_into().unwrap(),
-                offset_of!(Vertex, pos).try_into().unwrap(),
-            );
-            gl.enable_vertex_attrib_array(loc::VERT_POS);
-
-            gl.vertex_attrib_pointer_f32(
-                loc::TEXT_POS,
-                2,
-                glow::FLOAT,
-                false,
-                std::mem::size_of::<Vertex>().try_into().unwrap(),
-                offset_of!(Vertex, tex).try_into().unwrap(),
-            );
-            gl.enable_vertex_attrib_array(loc::TEXT_POS);
-
-            gl.vertex_attrib_pointer_i32(
-                loc::TEXT_IDX,
-                1,
-                glow::UNSIGNED_INT,
-                std::mem::size_of::<Vertex>().try_into().unwrap(),
-                offset_of!(Vertex, idx).try_into().unwrap(),
-            );
-            gl.enable_vertex_attrib_array(loc::TEXT_IDX);
-
-            gl.vertex_attrib_pointer_f32(
-                loc::VERT_COL,
-                4,
-                glow::FLOAT,
-                false,
-                std::mem::size_of::<Vertex>().try_into().unwrap(),
-                offset_of!(Vertex, color).try_into().unwrap(),
-            );
-            gl.enable_vertex_attrib_array(loc::VERT_COL);
-        }
+        let mut texture = Texture::new(&gl, Vec3::new(32, 32, TEXTURE_LAYERS))?;
+        let white = image::RgbaImage::from_pixel(32, 32, image::Rgba([255; 4]));
+        texture.update_image(&gl, WHITE, &image::DynamicImage::ImageRgba8(white));
+
+        let mut mesh = Mesh::<SpritePipeline>::default();
+        mesh.push_quad(Quad::unit());
+        // SAFETY: the mesh outlives the buffer creation.
+        let quad = unsafe { Buffer::immutable(&gl, glow::ARRAY_BUFFER, &mesh.data)? };
+
+        let layers = Layers::new(&gl, &quad)?;
+        let cursor = SpriteBatch::new(&gl, &quad, 1)?;
+        let belt_items = SpriteBatch::new(&gl, &quad, 1024)?;
+        let bars = SpriteBatch::new(&gl, &quad, 256)?;
+        let hud = SpriteBatch::new(&gl, &quad, 64)?;
+        let text = TextBatch::new(&gl, GlyphAtlas::ttf(crate::loader::FONT, 32.0)?)?;
+        let post = PostProcessor::new(&gl)?;
+        #[cfg(debug_assertions)]
+        let lines = LineBatch::new(&gl)?;
 
         Ok(Self {
-            vertex_array,
             program,
             texture,
-            vertex_buffer,
+            smooth: false,
+            quad,
+            layers,
+            cursor,
+            belt_items,
+            bars,
+            hud,
+            text,
+            post,
+
+            #[cfg(debug_assertions)]
+            lines,
 
             gl,
 
-            tile_mesh,
-
-            grid_size: Vec2::new(grid_height, grid_width),
-            frame_duration: Vec::new(),
+            coordinate_reader: None,
+            color_reader: None,
+            texture_reader: None,
+            position_reader: None,
+            size_reader: None,
+            tile_reader: None,
+            layer_reader: None,
+            rotation_reader: None,
+            footprint_reader: None,
+            changed: BitSet::new(),
+            removed: BitSet::new(),
         })
     }
 }
 
+/// Sorts the events of a flagged storage into changed and removed entities.
+fn read_events<C>(
+    storage: &ReadStorage<C>,
+    reader_id: &mut Option<ReaderId<ComponentEvent>>,
+    changed: &mut BitSet,
+    removed: &mut BitSet,
+) where
+    C: Component,
+    C::Storage: Tracked,
+{
+    for event in storage.channel().read(reader_id.as_mut().unwrap()) {
+        use ComponentEvent::{Inserted, Modified, Removed};
+        let _ = match event {
+            Inserted(id) | Modified(id) => changed.add(*id),
+            Removed(id) => removed.add(*id),
+        };
+    }
+}
+
 impl<'a> System<'a> for Renderer {
     type SystemData = (
         Entities<'a>,
         Read<'a, GameStart>,
+        Read<'a, DeltaTime>,
         Read<'a, Scene>,
+        Read<'a, Settings>,
         ReadStorage<'a, Color>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Coordinate>,
         ReadStorage<'a, TextureIndex>,
+        ReadStorage<'a, Size>,
+        ReadStorage<'a, Tile>,
+        ReadStorage<'a, RenderLayer>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Footprint>,
+        ReadStorage<'a, Text>,
+        ReadStorage<'a, Conveyor>,
+        ReadStorage<'a, ConstructionSite>,
+        ReadStorage<'a, Health>,
+        Read<'a, Items>,
+        Read<'a, BlendModes>,
+        Read<'a, PostProcessing>,
+        Write<'a, Capture>,
+        Write<'a, Hud>,
+        Write<'a, DebugDraw>,
+        Read<'a, Profiler>,
     );
 
     fn setup(&mut self, world: &mut World) {
         Self::SystemData::setup(world);
+        self.coordinate_reader = Some(world.write_storage::<Coordinate>().register_reader());
+        self.color_reader = Some(world.write_storage::<Color>().register_reader());
+        self.texture_reader = Some(world.write_storage::<TextureIndex>().register_reader());
+        self.position_reader = Some(world.write_storage::<Position>().register_reader());
+        self.size_reader = Some(world.write_storage::<Size>().register_reader());
+        self.tile_reader = Some(world.write_storage::<Tile>().register_reader());
+        self.layer_reader = Some(world.write_storage::<RenderLayer>().register_reader());
+        self.rotation_reader = Some(world.write_storage::<Rotation>().register_reader());
+        self.footprint_reader = Some(world.write_storage::<Footprint>().register_reader());
+
         let loader = world.fetch::<Loader>();
         for (idx, image) in loader.iter_images() {
             self.texture.update_image(&self.gl, idx as _, image);
@@ -235,83 +385,333 @@ impl<'a> System<'a> for Renderer {
 
     fn run(
         &mut self,
-        (entities, _start, scene, colors, _positions, coordinates, textures): Self::SystemData,
+        (
+            entities,
+            _start,
+            _delta,
+            scene,
+            settings,
+            colors,
+            positions,
+            coordinates,
+            textures,
+            sizes,
+            tiles,
+            render_layers,
+            rotations,
+            footprints,
+            texts,
+            conveyors,
+            sites,
+            healths,
+            items,
+            blend_modes,
+            post_processing,
+            mut capture,
+            mut overlay,
+            mut _debug_draw,
+            profiler,
+        ): Self::SystemData,
     ) {
+        let smooth = settings.filtering == Filtering::Linear;
+        if smooth != self.smooth {
+            self.texture.set_smooth(&self.gl, smooth);
+            self.smooth = smooth;
+        }
+
         let Self {
             gl,
-            tile_mesh,
-            vertex_buffer,
+            quad,
+            layers,
+            cursor,
+            belt_items,
+            bars,
+            hud,
+            text: text_batch,
+            post,
+            #[cfg(debug_assertions)]
+            lines,
+            coordinate_reader,
+            color_reader,
+            texture_reader,
+            position_reader,
+            size_reader,
+            tile_reader,
+            layer_reader,
+            rotation_reader,
+            footprint_reader,
+            changed,
+            removed,
             ..
         } = self;
 
+        // NOTE: with post-processing the scene is drawn offscreen first.
+        let screen_size = scene.screen_dimentions().map(|x| x.max(0) as u32);
+        let passes = &post_processing.passes;
+        let post_processed = !passes.is_empty()
+            && match post.begin(gl, screen_size) {
+                Ok(()) => true,
+                Err(err) => {
+                    crate::logger::log(&format!("failed to begin post-processing: {:?}", err));
+                    RenderTarget::unbind(gl);
+                    false
+                }
+            };
+
         unsafe {
             // expected blocking operation for vsync
             let x = 1.0;
             gl.clear_color(x, x, x, x);
-            gl.clear(glow::COLOR_BUFFER_BIT);
+            gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
         }
 
-        // let frame_start = Instant::now();
-
-        // TODO: this needs a way to handle changing vertices count.
-        // NOTE: this could be done much more efficiently,
-        // but we're not rendering thousands of tiles yet.
-        tile_mesh.clear();
-        for (_, coord, text, color) in (&*entities, &coordinates, &textures, &colors).join() {
-            let t = text.0.unwrap_or(0) as _;
-            let v = coord.0.numcast().unwrap();
-            tile_mesh.push_quad(Quad::rect(v, 0.0, t, color.0));
+        let build_start = Instant::now();
+        changed.clear();
+        removed.clear();
+        read_events(&coordinates, coordinate_reader, changed, removed);
+        read_events(&positions, position_reader, changed, removed);
+        read_events(&colors, color_reader, changed, removed);
+        read_events(&textures, texture_reader, changed, removed);
+
+        // NOTE: these only change how a sprite is drawn, so them being removed
+        // only means the sprite has to be moved or rebuilt.
+        let mut rebuilt = BitSet::new();
+        read_events(&sizes, size_reader, changed, &mut rebuilt);
+        read_events(&tiles, tile_reader, changed, &mut rebuilt);
+        read_events(&render_layers, layer_reader, changed, &mut rebuilt);
+        read_events(&rotations, rotation_reader, changed, &mut rebuilt);
+        read_events(&footprints, footprint_reader, changed, &mut rebuilt);
+        *changed |= &rebuilt;
+
+        // NOTE: removals come first, so that a component which is removed and
+        // inserted again within the same frame keeps it's sprite.
+        for id in (&*removed).join() {
+            let entity = entities.entity(id);
+            if (coordinates.get(entity).is_none() && positions.get(entity).is_none())
+                || colors.get(entity).is_none()
+                || textures.get(entity).is_none()
+            {
+                layers.remove(id);
+            }
         }
 
-        tile_mesh.push_quad(Quad::rect(
-            scene.coordinate_cursor().numcast().unwrap(),
-            0.0,
-            2,
-            Rgba::broadcast(0.7),
-        ));
+        for (
+            entity,
+            text,
+            color,
+            position,
+            coordinate,
+            size,
+            tile,
+            layer,
+            rotation,
+            footprint,
+            _,
+        ) in (
+            &*entities,
+            &textures,
+            &colors,
+            positions.maybe(),
+            coordinates.maybe(),
+            sizes.maybe(),
+            tiles.maybe(),
+            render_layers.maybe(),
+            rotations.maybe(),
+            footprints.maybe(),
+            &*changed,
+        )
+            .join()
+        {
+            let rotation = rotation.copied().unwrap_or_default();
+            let footprint = footprint.copied().unwrap_or_default();
+            let pos = match (position, coordinate) {
+                (Some(position), _) => position.0,
+                (None, Some(coordinate)) => footprint.center(coordinate.0, rotation).into(),
+                (None, None) => continue,
+            };
+            // NOTE: blocks with a footprint are as large as it, and everything
+            // else is square.
+            let size = match size {
+                Some(size) => Vec2::broadcast(size.0),
+                None => footprint.rotated(rotation).numcast().unwrap(),
+            };
+            let instance = Instance::new(pos, size, text.0.unwrap_or(0), color.0)
+                .rotated(rotation.quarter_turns());
+
+            let layer = match (layer, tile) {
+                (Some(layer), _) => layer.0,
+                (None, Some(_)) => Layer::Blocks,
+                (None, None) => Layer::Units,
+            };
+            // NOTE: only tiles and floors in tile space are static enough to be
+            // cached by chunk.
+            let chunk = coordinate
+                .filter(|_| tile.is_some() || layer == Layer::Floor)
+                .map(|c| c.0);
+
+            if let Err(err) = layers.insert(gl, quad, entity.id(), layer, chunk, instance) {
+                crate::logger::log(&format!("failed to create chunk: {:?}", err));
+            }
+        }
 
-        unsafe {
-            // SAFETY: safe if we don't mutate the mesh before drawing.
-            vertex_buffer.update(&gl, 0, &tile_mesh.data);
+        let highlight = Instance::tile(scene.coordinate_cursor(), 2, Rgba::broadcast(0.7));
+        if cursor.instances.get(0) != Some(&highlight) {
+            cursor.instances.insert(0, highlight);
+        }
 
-            let [x, y] = scene.screen_dimentions().into_array();
-            gl.viewport(0, 0, x, y);
-            gl.scissor(0, 0, x, y);
+        belt_items.instances.clear();
+        let belts = (&conveyors, &coordinates).join();
+        for (position, item) in belts.flat_map(|(conveyor, c)| conveyor.positions(c.0)) {
+            let color = items.get(item).color();
+            let instance = Instance::new(position.into(), Vec2::broadcast(0.3), WHITE, color);
+            let key = belt_items.instances.len() as u32;
+            belt_items.instances.insert(key, instance);
+        }
 
-            self.program.use_program(&gl);
-            self.program
-                .set_uniform(&gl, "transform", scene.transform());
-            self.texture.bind(&gl);
-            gl.bind_vertex_array(self.vertex_array);
+        // NOTE: only damaged blocks show their health, where sites never have
+        // any, so the bars don't overlap.
+        bars.instances.clear();
+        let progress = (&sites, &coordinates, footprints.maybe(), rotations.maybe())
+            .join()
+            .map(|(site, c, f, r)| (c, f, r, site.progress(), CONSTRUCTION));
+        let damaged = (
+            &healths,
+            &coordinates,
+            footprints.maybe(),
+            rotations.maybe(),
+        )
+            .join()
+            .filter(|(health, ..)| health.is_damaged())
+            .map(|(health, c, f, r)| {
+                let value = health.fraction();
+                let color = Lerp::lerp(HEALTH_LOW, HEALTH_HIGH, value);
+                (c, f, r, value, color)
+            });
+        for (coordinate, footprint, rotation, value, color) in progress.chain(damaged) {
+            let rotation = rotation.copied().unwrap_or_default();
+            let footprint = footprint.copied().unwrap_or_default();
+            let center = footprint.center(coordinate.0, rotation);
+            let size = footprint.rotated(rotation).numcast::<f32>().unwrap();
+            for instance in progress_bar(center, size, value, color) {
+                let key = bars.instances.len() as u32;
+                bars.instances.insert(key, instance);
+            }
+        }
 
-            gl.draw_arrays(
-                glow::TRIANGLES,
-                0,
-                (self.tile_mesh.data.len() * self.grid_size.product()) as _,
-            );
+        hud.instances.clear();
+        for (id, (bounds, idx, color)) in overlay.sprites.iter().enumerate() {
+            let size = Vec2::from(bounds.size());
+            let center = bounds.min + size / 2.0;
+            let instance = Instance::new(center.into(), size, *idx, *color);
+            hud.instances.insert(id as u32, instance);
+        }
 
-            // gl.draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_INT, 0);
-            gl.bind_vertex_array(None);
+        text_batch.build(
+            (
+                &texts,
+                positions.maybe(),
+                coordinates.maybe(),
+                colors.maybe(),
+                render_layers.maybe(),
+            )
+                .join()
+                .filter_map(|(content, position, coordinate, color, layer)| {
+                    let pos = match (position, coordinate) {
+                        (Some(position), _) => position.0,
+                        (None, Some(coordinate)) => coordinate.0.numcast::<f32>().unwrap().into(),
+                        (None, None) => return None,
+                    };
+                    let color = color.map(|color| color.0).unwrap_or(Rgba::black());
+                    let layer = layer.map(|layer| layer.0).unwrap_or(Layer::Overlay);
+                    Some((layer, pos, content, color))
+                })
+                .chain(overlay.texts.iter().map(|(position, text, color)| {
+                    (Layer::Ui, Vec3::from(*position), text, *color)
+                })),
+        );
+        let upload_start = Instant::now();
+        profiler.record("render/build", Category::Render, build_start, upload_start);
+
+        if let Err(err) = layers
+            .sync(gl, quad)
+            .and_then(|_| cursor.sync(gl, quad))
+            .and_then(|_| belt_items.sync(gl, quad))
+            .and_then(|_| bars.sync(gl, quad))
+            .and_then(|_| hud.sync(gl, quad))
+        {
+            crate::logger::log(&format!("failed to upload sprites: {:?}", err));
         }
 
-        // let now = Instant::now();
-        // let duration = now.duration_since(frame_start);
-        // self.frame_duration.push(duration);
+        if let Err(err) = text_batch.sync(gl) {
+            crate::logger::log(&format!("failed to upload text: {:?}", err));
+        }
 
-        // if frame.0 % 100 == 0 {
-        //     let n: u32 = self.frame_duration.len().try_into().unwrap();
-        //     let avg_duration = self
-        //         .frame_duration
-        //         .iter()
-        //         .fold(Duration::new(0, 0), |a, b| a + *b)
-        //         / n;
+        #[cfg(debug_assertions)]
+        if let Err(err) = lines.sync(gl, &mut _debug_draw, _delta.0.as_secs_f32()) {
+            crate::logger::log(&format!("failed to upload debug lines: {:?}", err));
+        }
 
-        //     self.frame_duration.clear();
+        let draw_start = Instant::now();
+        profiler.record("render/upload", Category::Render, upload_start, draw_start);
 
-        //     // println!("draw ({:>3} μs)", avg_duration.as_micros());
+        unsafe {
+            let [x, y] = scene.screen_dimentions().into_array();
+            gl.viewport(0, 0, x, y);
+            gl.scissor(0, 0, x, y);
 
-        //     let cursor = scene.world_cursor().round();
-        //     // println!("cursor ({:>3}, {:>3})", cursor.x, cursor.y);
-        // }
+            self.texture.bind(gl);
+
+            let screen = Aabr {
+                min: Vec2::zero(),
+                max: scene.logical_dimensions(),
+            };
+
+            for (layer, batch) in layers.iter() {
+                let (transform, bounds) = match batch.state.space {
+                    Space::World => (scene.transform(), scene.visible_bounds()),
+                    Space::Screen => (scene.screen_transform(), screen),
+                };
+
+                let blend = blend_modes.get(*layer);
+                self.program.use_program(gl);
+                self.program.set_uniform(gl, "transform", transform);
+                self.program
+                    .set_uniform(gl, "premultiply", blend.premultiply());
+                batch.state.apply(gl);
+                blend.apply(gl);
+                batch.draw(gl, bounds);
+
+                match layer {
+                    Layer::Blocks => belt_items.draw(gl),
+                    Layer::Overlay => {
+                        bars.draw(gl);
+                        cursor.draw(gl);
+                    }
+                    Layer::Ui => hud.draw(gl),
+                    _ => {}
+                }
+
+                // NOTE: text is drawn over the sprites of it's own layer.
+                text_batch.draw(gl, *layer, transform, blend.premultiply());
+            }
+
+            #[cfg(debug_assertions)]
+            lines.draw(gl, scene.transform(), scene.screen_transform());
+
+            if post_processed {
+                post.apply(gl, passes, screen_size);
+            }
+        }
+        overlay.clear();
+        // NOTE: this is only the time taken to submit draw calls, because the
+        // GPU draws them asynchronously.
+        profiler.record("render/draw", Category::Render, draw_start, Instant::now());
+
+        if capture.is_requested() {
+            let image = read_pixels(gl, screen_size);
+            if let Err(err) = capture.save(image) {
+                crate::logger::log(&format!("failed to save capture: {:?}", err));
+            }
+        }
     }
 }
@@ -0,0 +1,146 @@
+//! Full screen post-processing passes, which are applied to the scene after
+//! it was drawn into an offscreen render target.
+
+use super::*;
+
+use glow::*;
+use serde::{Deserialize, Serialize};
+
+/// A single post-processing pass, with it's parameters, which is written in
+/// settings as `{ "effect": "vignette", "strength": 0.5 }`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "effect", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PostEffect {
+    /// Darkens the edges of the screen.
+    Vignette { strength: f32 },
+
+    /// Scales brightness by `exposure`, and adjusts contrast and saturation,
+    /// where `1.0` leaves them unchanged.
+    ColorGrading {
+        exposure: f32,
+        contrast: f32,
+        saturation: f32,
+    },
+
+    /// Draws the screen as blocks of `size` pixels.
+    Pixelate { size: f32 },
+
+    /// Makes colors brighter than `threshold` bleed into their surroundings.
+    Bloom { threshold: f32, intensity: f32 },
+}
+
+/// Resource which configures the chain of post-processing passes, which are
+/// applied in order; the scene is drawn directly to the screen without any.
+#[derive(Clone, Debug, Default)]
+pub struct PostProcessing {
+    pub passes: Vec<PostEffect>,
+}
+
+/// Programs and targets used to apply post-processing passes.
+pub struct PostProcessor {
+    vignette: Program,
+    grading: Program,
+    pixelate: Program,
+    bloom: Program,
+
+    /// Empty vertex array, because full screen passes generate their vertices.
+    vertex_array: Option<VertexArrayId>,
+
+    /// Targets which passes read from and draw into in turns, where the first
+    /// one is where the scene is drawn.
+    targets: [RenderTarget; 2],
+}
+
+impl PostProcessor {
+    pub fn new(gl: &Context) -> Result<Self, RenderError> {
+        let vss = include_str!("shaders/post_vss.glsl");
+        let program = |fss| Program::new(gl, vss, fss, &[]);
+
+        let vignette = program(include_str!("shaders/post_vignette_fss.glsl"))?;
+        let grading = program(include_str!("shaders/post_grading_fss.glsl"))?;
+        let pixelate = program(include_str!("shaders/post_pixelate_fss.glsl"))?;
+        let bloom = program(include_str!("shaders/post_bloom_fss.glsl"))?;
+
+        let vertex_array = Some(unsafe { gl.create_vertex_array()? });
+        let targets = [
+            RenderTarget::new(gl, Vec2::one())?,
+            RenderTarget::new(gl, Vec2::one())?,
+        ];
+
+        Ok(Self {
+            vignette,
+            grading,
+            pixelate,
+            bloom,
+            vertex_array,
+            targets,
+        })
+    }
+
+    /// Resizes the targets to the screen, and redirects drawing into the first
+    /// one, so the scene can be processed afterwards.
+    pub fn begin(&mut self, gl: &Context, size: Vec2<u32>) -> Result<(), RenderError> {
+        for target in self.targets.iter_mut() {
+            target.resize(gl, size)?;
+        }
+        self.targets[0].bind(gl);
+        Ok(())
+    }
+
+    /// Applies every pass to the scene, where the last pass draws to the screen.
+    pub unsafe fn apply(&self, gl: &Context, passes: &[PostEffect], size: Vec2<u32>) {
+        gl.disable(glow::DEPTH_TEST);
+        gl.disable(glow::BLEND);
+        gl.bind_vertex_array(self.vertex_array);
+
+        let resolution: Vec2<f32> = size.numcast().unwrap();
+        for (i, pass) in passes.iter().enumerate() {
+            let source = &self.targets[i % 2];
+            if i + 1 == passes.len() {
+                RenderTarget::unbind(gl);
+                gl.viewport(0, 0, size.x as i32, size.y as i32);
+            } else {
+                self.targets[(i + 1) % 2].bind(gl);
+            }
+
+            let program = match pass {
+                PostEffect::Vignette { .. } => &self.vignette,
+                PostEffect::ColorGrading { .. } => &self.grading,
+                PostEffect::Pixelate { .. } => &self.pixelate,
+                PostEffect::Bloom { .. } => &self.bloom,
+            };
+
+            program.use_program(gl);
+            program.set_uniform(gl, "resolution", resolution);
+            match *pass {
+                PostEffect::Vignette { strength } => {
+                    program.set_uniform(gl, "strength", strength);
+                }
+                PostEffect::ColorGrading {
+                    exposure,
+                    contrast,
+                    saturation,
+                } => {
+                    program.set_uniform(gl, "exposure", exposure);
+                    program.set_uniform(gl, "contrast", contrast);
+                    program.set_uniform(gl, "saturation", saturation);
+                }
+                PostEffect::Pixelate { size } => {
+                    program.set_uniform(gl, "size", size);
+                }
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                } => {
+                    program.set_uniform(gl, "threshold", threshold);
+                    program.set_uniform(gl, "intensity", intensity);
+                }
+            }
+
+            source.color().bind_to(gl, 0);
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        }
+
+        gl.bind_vertex_array(None);
+    }
+}
@@ -80,7 +80,10 @@ impl Program {
     }
 
     pub unsafe fn set_uniform<T: Uniform>(&self, gl: &Context, name: &str, value: T) {
-        let location = gl.get_uniform_location(self.program_id.unwrap(), name);
-        value.set_as_uniform(gl, location);
+        // NOTE: uniforms which don't exist, or were optimized out, have no location,
+        // and setting them would write into the uniform at location zero instead.
+        if let Some(location) = gl.get_uniform_location(self.program_id.unwrap(), name) {
+            value.set_as_uniform(gl, Some(location));
+        }
     }
 }
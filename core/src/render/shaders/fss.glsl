@@ -9,10 +9,21 @@ flat in uint idx;
 out vec4 color;
 
 uniform sampler2DArray tex;
+uniform bool premultiply;
 // uniform sampler2D tex;
 
 void main() {
   color = texture(tex, vec3(f_text_pos, idx)) * frag_col;
+
+  // NOTE: fully transparent texels must not write depth, or they would hide
+  // the sprites which are drawn after them.
+  if (color.a == 0.0) {
+    discard;
+  }
+
+  if (premultiply) {
+    color.rgb *= color.a;
+  }
   // color = texture(tex, f_text_pos) * frag_col;
   // color = vec4(1.0, 0.0, 0.0, 1.0);
 }
@@ -0,0 +1,10 @@
+#version 300 es
+precision mediump float;
+
+in vec4 frag_col;
+
+out vec4 color;
+
+void main() {
+  color = frag_col;
+}
@@ -0,0 +1,12 @@
+#version 300 es
+in vec2 vert_pos;
+in vec4 inst_col;
+
+out vec4 frag_col;
+
+uniform mat4 transform;
+
+void main() {
+  frag_col = inst_col;
+  gl_Position = transform * vec4(vert_pos, 0.0, 1.0);
+}
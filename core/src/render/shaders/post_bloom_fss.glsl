@@ -0,0 +1,31 @@
+#version 300 es
+precision mediump float;
+
+in vec2 f_text_pos;
+out vec4 color;
+
+uniform sampler2D source;
+uniform vec2 resolution;
+uniform float threshold;
+uniform float intensity;
+
+void main() {
+  color = texture(source, f_text_pos);
+
+  // NOTE: a single pass approximation, which blurs the bright parts of the
+  // scene with a sparse gaussian kernel, and adds them back on top.
+  vec2 texel = 2.0 / resolution;
+  vec3 glow = vec3(0.0);
+  float total = 0.0;
+  for (int x = -3; x <= 3; x++) {
+    for (int y = -3; y <= 3; y++) {
+      float weight = exp(-float(x * x + y * y) / 8.0);
+      vec3 rgb = texture(source, f_text_pos + vec2(x, y) * texel).rgb;
+      float brightness = max(rgb.r, max(rgb.g, rgb.b));
+      glow += rgb * step(threshold, brightness) * weight;
+      total += weight;
+    }
+  }
+
+  color.rgb += glow / total * intensity;
+}
@@ -0,0 +1,18 @@
+#version 300 es
+precision mediump float;
+
+in vec2 f_text_pos;
+out vec4 color;
+
+uniform sampler2D source;
+uniform float exposure;
+uniform float contrast;
+uniform float saturation;
+
+void main() {
+  color = texture(source, f_text_pos);
+  vec3 rgb = color.rgb * exposure;
+  rgb = (rgb - 0.5) * contrast + 0.5;
+  float luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+  color.rgb = clamp(mix(vec3(luma), rgb, saturation), 0.0, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 300 es
+precision mediump float;
+
+in vec2 f_text_pos;
+out vec4 color;
+
+uniform sampler2D source;
+uniform vec2 resolution;
+uniform float size;
+
+void main() {
+  // NOTE: samples the center of the block of pixels the fragment is in.
+  vec2 block = max(size, 1.0) / resolution;
+  color = texture(source, (floor(f_text_pos / block) + 0.5) * block);
+}
@@ -0,0 +1,14 @@
+#version 300 es
+precision mediump float;
+
+in vec2 f_text_pos;
+out vec4 color;
+
+uniform sampler2D source;
+uniform float strength;
+
+void main() {
+  color = texture(source, f_text_pos);
+  vec2 offset = f_text_pos - 0.5;
+  color.rgb *= clamp(1.0 - dot(offset, offset) * strength * 2.0, 0.0, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 300 es
+out vec2 f_text_pos;
+
+void main() {
+  // NOTE: a single triangle which covers the whole screen, generated from the
+  // vertex id, so that full screen passes don't need a vertex buffer.
+  vec2 pos = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+  f_text_pos = pos;
+  gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,23 @@
+#version 300 es
+precision mediump float;
+
+in vec2 f_text_pos;
+in vec4 frag_col;
+
+out vec4 color;
+
+uniform sampler2D atlas;
+uniform bool premultiply;
+
+void main() {
+  // NOTE: glyphs of TTF fonts are white, with their coverage as alpha.
+  color = texture(atlas, f_text_pos) * frag_col;
+
+  if (color.a == 0.0) {
+    discard;
+  }
+
+  if (premultiply) {
+    color.rgb *= color.a;
+  }
+}
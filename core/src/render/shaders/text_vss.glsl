@@ -0,0 +1,15 @@
+#version 300 es
+in vec3 vert_pos;
+in vec2 text_pos;
+in vec4 inst_col;
+
+out vec2 f_text_pos;
+out vec4 frag_col;
+
+uniform mat4 transform;
+
+void main() {
+  f_text_pos = text_pos;
+  frag_col = inst_col;
+  gl_Position = transform * vec4(vert_pos, 1.0);
+}
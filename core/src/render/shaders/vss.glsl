@@ -1,8 +1,12 @@
 #version 300 es
 in vec2 vert_pos;
 in vec2 text_pos;
+
+in vec3 inst_pos;
+in vec2 inst_size;
 in uint text_idx;
-in vec4 vert_col;
+in vec4 inst_col;
+in uint inst_rot;
 
 out vec2 f_text_pos;
 out vec4 frag_col;
@@ -11,28 +15,23 @@ flat out uint idx;
 uniform mat4 transform;
 
 void main() {
-  f_text_pos = text_pos;
+  // NOTE: sprites are turned counter-clockwise by sampling their texture
+  // turned the other way, which keeps their size axis-aligned.
+  vec2 t = text_pos - 0.5;
+  if (inst_rot == 1u) {
+    t = vec2(t.y, -t.x);
+  } else if (inst_rot == 2u) {
+    t = -t;
+  } else if (inst_rot == 3u) {
+    t = vec2(-t.y, t.x);
+  }
+  f_text_pos = t + 0.5;
   idx = text_idx;
-  frag_col = vert_col;
-  
-  vec4 pos = vec4(vert_pos, 1.0, 1.0);
-  
-  // mat4 tile = mat4(vec4(1.0, 0.0, 0.0, 0.0),
-  //                  vec4(0.0, 1.0, 0.0, 0.0),
-  //                  vec4(0.0, 0.0, 1.0, 0.0),
-  //                  vec4(tile_pos.xy, tile_pos.z*0.01, 1.0));
+  frag_col = inst_col;
 
-  // mat4 size = mat4(vec4(tile_size, 0.0, 0.0, 0.0),
-  //                  vec4(0.0, tile_size, 0.0, 0.0),
-  //                  vec4(0.0, 0.0, 1.0, 0.0),
-  //                  vec4(0.0, 0.0, 0.0, 1.0));
+  // NOTE: the unit quad is centered on the origin, so sprites are scaled
+  // around their center, and then moved into place.
+  vec4 pos = vec4(inst_pos.xy + vert_pos * inst_size, inst_pos.z, 1.0);
 
-  // mat4 size_pos = mat4(vec4(1.0, 0.0, 0.0, 0.0),
-  //                      vec4(0.0, 1.0, 0.0, 0.0),
-  //                      vec4(0.0, 0.0, 1.0, 0.0),
-  //                      vec4(tile_size/2.0,
-  //                           tile_size/2.0,
-  //                           0.0, 1.0));
-  
   gl_Position = transform * pos;
 }
@@ -0,0 +1,125 @@
+use super::*;
+
+use glow::*;
+use std::mem::size_of;
+
+/// Group of sprite instances drawn over the shared unit quad, with a single
+/// instanced draw call.
+pub struct SpriteBatch {
+    pub instances: Instances,
+
+    /// Whether instances are sorted back to front before they're uploaded.
+    pub sorted: bool,
+
+    buffer: Buffer<Instance>,
+    vertex_array: Option<VertexArrayId>,
+}
+
+impl SpriteBatch {
+    pub fn new(gl: &Context, quad: &Buffer<Vertex>, size: usize) -> Result<Self, RenderError> {
+        let buffer = Buffer::dynamic(gl, glow::ARRAY_BUFFER, size)?;
+        let vertex_array;
+        unsafe {
+            vertex_array = Some(gl.create_vertex_array()?);
+            gl.bind_vertex_array(vertex_array);
+            bind_attributes(gl, quad, &buffer);
+            gl.bind_vertex_array(None);
+        }
+
+        Ok(Self {
+            instances: Instances::default(),
+            sorted: false,
+            buffer,
+            vertex_array,
+        })
+    }
+
+    /// Uploads the instances which changed since the last call, and grows the
+    /// buffer when the instances no longer fit into it.
+    pub fn sync(&mut self, gl: &Context, quad: &Buffer<Vertex>) -> Result<(), RenderError> {
+        let Self {
+            instances,
+            sorted,
+            buffer,
+            vertex_array,
+        } = self;
+
+        if *sorted {
+            instances.sort();
+        }
+
+        if instances.len() > buffer.size() {
+            let size = instances.len().next_power_of_two();
+            buffer._delete(gl);
+            *buffer = Buffer::dynamic(gl, glow::ARRAY_BUFFER, size)?;
+            unsafe {
+                gl.bind_vertex_array(*vertex_array);
+                bind_attributes(gl, quad, buffer);
+                gl.bind_vertex_array(None);
+            }
+            instances.invalidate();
+        }
+
+        let ranges = instances.take_dirty();
+        let data = instances.as_slice();
+        for range in ranges {
+            unsafe {
+                // SAFETY: the instances aren't mutated until they are drawn.
+                buffer.update(gl, range.start, &data[range]);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub unsafe fn draw(&self, gl: &Context) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        gl.bind_vertex_array(self.vertex_array);
+        gl.draw_arrays_instanced(glow::TRIANGLES, 0, 6, self.instances.len() as _);
+        gl.bind_vertex_array(None);
+    }
+}
+
+/// Points the attributes of the bound vertex array at the quad vertices, and
+/// at the instances which advance once per quad.
+#[rustfmt::skip]
+unsafe fn bind_attributes(gl: &Context, quad: &Buffer<Vertex>, instances: &Buffer<Instance>) {
+    attributes(gl, quad, 0, &[
+        (loc::VERT_POS, 2, glow::FLOAT, offset_of!(Vertex, pos)),
+        (loc::TEXT_POS, 2, glow::FLOAT, offset_of!(Vertex, tex)),
+    ]);
+
+    attributes(gl, instances, 1, &[
+        (loc::INST_POS, 3, glow::FLOAT, offset_of!(Instance, pos)),
+        (loc::INST_SIZE, 2, glow::FLOAT, offset_of!(Instance, size)),
+        (loc::TEXT_IDX, 1, glow::UNSIGNED_INT, offset_of!(Instance, idx)),
+        (loc::INST_COL, 4, glow::FLOAT, offset_of!(Instance, color)),
+        (loc::INST_ROT, 1, glow::UNSIGNED_INT, offset_of!(Instance, rotation)),
+    ]);
+}
+
+/// Configures and enables attributes, as `(location, size, type, offset)`, which
+/// read the elements of a buffer and advance every `divisor` instances.
+pub(super) unsafe fn attributes<T: Copy + Pod>(
+    gl: &Context,
+    buffer: &Buffer<T>,
+    divisor: u32,
+    attributes: &[(u32, i32, u32, usize)],
+) {
+    let stride = size_of::<T>().try_into().unwrap();
+    buffer.bind(gl);
+    for &(location, size, data_type, offset) in attributes {
+        let offset = offset.try_into().unwrap();
+        match data_type {
+            glow::INT | glow::UNSIGNED_INT => {
+                gl.vertex_attrib_pointer_i32(location, size, data_type, stride, offset)
+            }
+            _ => gl.vertex_attrib_pointer_f32(location, size, data_type, false, stride, offset),
+        }
+        gl.vertex_attrib_divisor(location, divisor);
+        gl.enable_vertex_attrib_array(location);
+    }
+}
@@ -0,0 +1,106 @@
+use super::*;
+
+use glow::*;
+
+/// Offscreen framebuffer with a color texture, which can be sampled after it
+/// was drawn into, and a depth buffer for the depth tested layers.
+pub struct RenderTarget {
+    framebuffer_id: Option<FramebufferId>,
+    depth_id: Option<RenderbufferId>,
+    color: Texture,
+    size: Vec2<u32>,
+}
+
+impl RenderTarget {
+    pub fn new(gl: &Context, size: Vec2<u32>) -> Result<Self, RenderError> {
+        let size = size.map(|x| x.max(1));
+        let color = Texture::target(gl, size)?;
+
+        let framebuffer_id;
+        let depth_id;
+        unsafe {
+            framebuffer_id = Some(gl.create_framebuffer()?);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, framebuffer_id);
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                color.texture_id(),
+                0,
+            );
+
+            depth_id = Some(gl.create_renderbuffer()?);
+            gl.bind_renderbuffer(glow::RENDERBUFFER, depth_id);
+            gl.renderbuffer_storage(
+                glow::RENDERBUFFER,
+                glow::DEPTH_COMPONENT24,
+                size.x as i32,
+                size.y as i32,
+            );
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_ATTACHMENT,
+                glow::RENDERBUFFER,
+                depth_id,
+            );
+
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                return Err(RenderError::FramebufferIncomplete(status));
+            }
+        }
+
+        Ok(Self {
+            framebuffer_id,
+            depth_id,
+            color,
+            size,
+        })
+    }
+
+    /// Texture which holds whatever was drawn into the target.
+    pub fn color(&self) -> &Texture {
+        &self.color
+    }
+
+    /// Recreates the attachments when the size changed, which is expected to
+    /// follow the size of the window.
+    pub fn resize(&mut self, gl: &Context, size: Vec2<u32>) -> Result<(), RenderError> {
+        if self.size != size.map(|x| x.max(1)) {
+            let target = Self::new(gl, size)?;
+            self._delete(gl);
+            *self = target;
+        }
+        Ok(())
+    }
+
+    /// Redirects drawing into the target.
+    pub fn bind(&self, gl: &Context) {
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, self.framebuffer_id);
+            gl.viewport(0, 0, self.size.x as i32, self.size.y as i32);
+        }
+    }
+
+    /// Redirects drawing back into the default framebuffer.
+    pub fn unbind(gl: &Context) {
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+    }
+
+    pub fn _delete(&self, gl: &Context) {
+        unsafe {
+            if let Some(id) = self.framebuffer_id {
+                gl.delete_framebuffer(id);
+            }
+            if let Some(id) = self.depth_id {
+                gl.delete_renderbuffer(id);
+            }
+        }
+        self.color._delete(gl);
+    }
+}
@@ -0,0 +1,324 @@
+//! Text layout, and the pipeline which draws laid out glyphs as quads sampling
+//! the glyph atlas.
+
+use super::*;
+
+use glow::*;
+use std::ops::Range;
+
+/// Horizontal alignment of lines relative to the position of a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Glyph placed relative to the top of a text, in units of lines, where y
+/// goes down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacedGlyph {
+    pub bounds: Aabr<f32>,
+    pub uv: Aabr<f32>,
+}
+
+/// Lays out text into glyphs, where lines are broken on newlines, and between
+/// words once they would be wider than `wrap` lines.
+pub fn layout(
+    atlas: &mut GlyphAtlas,
+    text: &str,
+    align: Align,
+    wrap: Option<f32>,
+) -> Vec<PlacedGlyph> {
+    let scale = 1.0 / atlas.line_height();
+    let ascent = atlas.ascent() * scale;
+    let wrap = wrap.unwrap_or(f32::INFINITY);
+    let advance =
+        |atlas: &mut GlyphAtlas, c| atlas.glyph(c).map(|g| g.advance * scale).unwrap_or(0.0);
+
+    let mut lines: Vec<(String, f32)> = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut width = 0.0;
+
+        for (i, word) in paragraph.split(' ').enumerate() {
+            let word_width: f32 = word.chars().map(|c| advance(atlas, c)).sum();
+            let space = if i == 0 { 0.0 } else { advance(atlas, ' ') };
+
+            // NOTE: the space a line is broken on is dropped.
+            if !line.is_empty() && width + space + word_width > wrap {
+                lines.push((std::mem::take(&mut line), width));
+                width = 0.0;
+            } else if i > 0 {
+                line.push(' ');
+                width += space;
+            }
+            line.push_str(word);
+            width += word_width;
+        }
+        lines.push((line, width));
+    }
+
+    let mut glyphs = Vec::new();
+    for (row, (line, width)) in lines.into_iter().enumerate() {
+        let mut pen = Vec2::new(
+            match align {
+                Align::Left => 0.0,
+                Align::Center => -width / 2.0,
+                Align::Right => -width,
+            },
+            row as f32 + ascent,
+        );
+
+        for c in line.chars() {
+            let glyph = match atlas.glyph(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if !glyph.is_blank() {
+                glyphs.push(PlacedGlyph {
+                    bounds: Aabr {
+                        min: pen + glyph.bounds.min * scale,
+                        max: pen + glyph.bounds.max * scale,
+                    },
+                    uv: glyph.uv,
+                });
+            }
+            pen.x += glyph.advance * scale;
+        }
+    }
+    glyphs
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+#[repr(C, packed)]
+pub struct GlyphVertex {
+    pos: [f32; 3],
+    tex: [f32; 2],
+    color: [f32; 4],
+}
+
+unsafe impl Pod for GlyphVertex {}
+
+/// Text of every layer, which is laid out again every frame, because text
+/// is expected to be short and to change often.
+pub struct TextBatch {
+    pub atlas: GlyphAtlas,
+    texture: Texture,
+    program: Program,
+
+    vertices: Vec<GlyphVertex>,
+
+    /// Vertices of every layer, indexed by layer.
+    ranges: [Range<usize>; 6],
+
+    buffer: Buffer<GlyphVertex>,
+    vertex_array: Option<VertexArrayId>,
+}
+
+impl TextBatch {
+    pub fn new(gl: &Context, atlas: GlyphAtlas) -> Result<Self, RenderError> {
+        let program = Program::new(
+            gl,
+            include_str!("shaders/text_vss.glsl"),
+            include_str!("shaders/text_fss.glsl"),
+            &[
+                (loc::VERT_POS, "vert_pos"),
+                (loc::TEXT_POS, "text_pos"),
+                (loc::INST_COL, "inst_col"),
+            ],
+        )?;
+
+        let (width, height) = atlas.image().dimensions();
+        let size = Vec2::new(width, height);
+        let texture = Texture::atlas(gl, size, atlas.smooth())?;
+
+        let buffer = Buffer::dynamic(gl, glow::ARRAY_BUFFER, 1024)?;
+        let vertex_array = Some(unsafe { gl.create_vertex_array()? });
+        unsafe { Self::bind_attributes(gl, vertex_array, &buffer) };
+
+        Ok(Self {
+            atlas,
+            texture,
+            program,
+            vertices: Vec::new(),
+            ranges: Default::default(),
+            buffer,
+            vertex_array,
+        })
+    }
+
+    #[rustfmt::skip]
+    unsafe fn bind_attributes(
+        gl: &Context,
+        vertex_array: Option<VertexArrayId>,
+        buffer: &Buffer<GlyphVertex>,
+    ) {
+        gl.bind_vertex_array(vertex_array);
+        attributes(gl, buffer, 0, &[
+            (loc::VERT_POS, 3, glow::FLOAT, offset_of!(GlyphVertex, pos)),
+            (loc::TEXT_POS, 2, glow::FLOAT, offset_of!(GlyphVertex, tex)),
+            (loc::INST_COL, 4, glow::FLOAT, offset_of!(GlyphVertex, color)),
+        ]);
+        gl.bind_vertex_array(None);
+    }
+
+    /// Lays out the text of every layer, where texts are `(layer, position,
+    /// text, color)`, and positions in world-space are flipped to go up.
+    pub fn build<'a>(
+        &mut self,
+        texts: impl Iterator<Item = (Layer, Vec3<f32>, &'a Text, Rgba<f32>)>,
+    ) {
+        let mut texts: Vec<_> = texts.collect();
+        texts.sort_by_key(|(layer, ..)| *layer);
+
+        self.vertices.clear();
+        self.ranges = Default::default();
+        for (layer, position, text, color) in texts {
+            let flip = match layer.state().space {
+                Space::World => Vec2::new(1.0, -1.0),
+                Space::Screen => Vec2::one(),
+            };
+
+            let start = self.vertices.len();
+            for glyph in layout(&mut self.atlas, &text.content, text.align, text.wrap) {
+                let corner = |x: f32, y: f32, u: f32, v: f32| {
+                    let pos = position.xy() + Vec2::new(x, y) * flip * text.size;
+                    GlyphVertex {
+                        pos: [pos.x, pos.y, position.z],
+                        tex: [u, v],
+                        color: color.into_array(),
+                    }
+                };
+
+                let PlacedGlyph { bounds: b, uv } = glyph;
+                let top_left = corner(b.min.x, b.min.y, uv.min.x, uv.min.y);
+                let top_right = corner(b.max.x, b.min.y, uv.max.x, uv.min.y);
+                let bottom_left = corner(b.min.x, b.max.y, uv.min.x, uv.max.y);
+                let bottom_right = corner(b.max.x, b.max.y, uv.max.x, uv.max.y);
+                self.vertices.extend_from_slice(&[
+                    top_left,
+                    bottom_left,
+                    bottom_right,
+                    top_left,
+                    bottom_right,
+                    top_right,
+                ]);
+            }
+
+            let range = &mut self.ranges[layer as usize];
+            if range.start == range.end {
+                *range = start..start;
+            }
+            range.end = self.vertices.len();
+        }
+    }
+
+    /// Uploads the vertices and any glyphs which were added to the atlas.
+    pub fn sync(&mut self, gl: &Context) -> Result<(), RenderError> {
+        if self.atlas.take_dirty() {
+            let image = image::DynamicImage::ImageRgba8(self.atlas.image().clone());
+            self.texture.update_image(gl, 0, &image);
+        }
+
+        if self.vertices.len() > self.buffer.size() {
+            self.buffer._delete(gl);
+            self.buffer = Buffer::dynamic(
+                gl,
+                glow::ARRAY_BUFFER,
+                self.vertices.len().next_power_of_two(),
+            )?;
+            unsafe { Self::bind_attributes(gl, self.vertex_array, &self.buffer) };
+        }
+
+        unsafe {
+            // SAFETY: the vertices aren't mutated until they are drawn.
+            self.buffer.update(gl, 0, &self.vertices);
+        }
+        Ok(())
+    }
+
+    /// Draws the text of a layer, leaving the text program in use.
+    pub unsafe fn draw(&self, gl: &Context, layer: Layer, transform: Mat4<f32>, premultiply: bool) {
+        let range = self.ranges[layer as usize].clone();
+        if range.start == range.end {
+            return;
+        }
+
+        self.program.use_program(gl);
+        self.program.set_uniform(gl, "transform", transform);
+        self.program.set_uniform(gl, "premultiply", premultiply);
+        self.texture.bind(gl);
+
+        gl.bind_vertex_array(self.vertex_array);
+        gl.draw_arrays(glow::TRIANGLES, range.start as i32, range.len() as i32);
+        gl.bind_vertex_array(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    /// Monospace font of 8 by 8 cells, for characters from space onwards.
+    fn atlas() -> GlyphAtlas {
+        GlyphAtlas::bitmap(RgbaImage::new(128, 48), Vec2::new(8, 8), ' ')
+    }
+
+    fn lines(glyphs: &[PlacedGlyph]) -> Vec<(f32, f32)> {
+        glyphs
+            .iter()
+            .map(|g| (g.bounds.min.x, g.bounds.min.y))
+            .collect()
+    }
+
+    #[test]
+    fn layout_align_test() {
+        let mut atlas = atlas();
+        assert_eq!(
+            lines(&layout(&mut atlas, "ab", Align::Left, None)),
+            vec![(0.0, 0.0), (1.0, 0.0)]
+        );
+        assert_eq!(
+            lines(&layout(&mut atlas, "ab", Align::Center, None)),
+            vec![(-1.0, 0.0), (0.0, 0.0)]
+        );
+        assert_eq!(
+            lines(&layout(&mut atlas, "ab", Align::Right, None)),
+            vec![(-2.0, 0.0), (-1.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn layout_wrap_test() {
+        let mut atlas = atlas();
+        let glyphs = layout(&mut atlas, "ab cd\ne", Align::Left, Some(3.0));
+        assert_eq!(
+            lines(&glyphs),
+            vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.0, 2.0)]
+        );
+
+        // NOTE: words longer than a line aren't broken.
+        assert_eq!(
+            layout(&mut atlas, "abcdef", Align::Left, Some(3.0)).len(),
+            6
+        );
+    }
+
+    #[test]
+    fn ttf_atlas_test() {
+        let mut atlas = GlyphAtlas::ttf(crate::loader::FONT, 32.0).unwrap();
+        assert!(atlas.take_dirty());
+
+        let glyph = atlas.glyph('A').unwrap();
+        assert!(glyph.advance > 0.0);
+        assert!(!glyph.is_blank());
+        assert!(atlas.take_dirty());
+
+        assert_eq!(atlas.glyph('A'), Some(glyph));
+        assert!(!atlas.take_dirty());
+        assert!(atlas.glyph(' ').unwrap().is_blank());
+    }
+}
@@ -13,8 +13,7 @@ const BORDER: i32 = 0;
 enum Type {
     Texture2dArray,
 
-    /// Texture2d currently exists mainly for testing purposes.
-    #[allow(dead_code)]
+    /// Texture2d is used for render targets.
     Texture2d,
 }
 
@@ -129,6 +128,86 @@ impl Texture {
         })
     }
 
+    /// Creates an empty texture for a render target to draw into, which is
+    /// filtered linearly because it's sampled by full screen passes.
+    pub fn target(gl: &Context, size: Vec2<u32>) -> Result<Self, RenderError> {
+        Self::texture_2d(gl, size, glow::LINEAR)
+    }
+
+    /// Creates an empty texture for a glyph atlas, where bitmap fonts aren't
+    /// filtered `smooth`ly, so that their pixels stay sharp.
+    pub fn atlas(gl: &Context, size: Vec2<u32>, smooth: bool) -> Result<Self, RenderError> {
+        let filter = if smooth { glow::LINEAR } else { glow::NEAREST };
+        Self::texture_2d(gl, size, filter)
+    }
+
+    fn texture_2d(gl: &Context, size: Vec2<u32>, filter: u32) -> Result<Self, RenderError> {
+        let texture_type = Type::Texture2d;
+        let size = Vec3::new(size.x, size.y, 1).numcast().unwrap();
+        let level = 0;
+        let slot = 0;
+
+        let texture_id;
+        unsafe {
+            texture_id = Some(gl.create_texture()?);
+
+            gl.active_texture(glow::TEXTURE0 + slot);
+            gl.bind_texture(texture_type.into_gl(), texture_id);
+            gl.tex_image_2d(
+                texture_type.into_gl(),
+                level,
+                glow::RGBA as i32,
+                size.x,
+                size.y,
+                BORDER,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                None,
+            );
+
+            for (parameter, value) in [
+                (glow::TEXTURE_MIN_FILTER, filter),
+                (glow::TEXTURE_MAG_FILTER, filter),
+                (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+                (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+            ]
+            .iter()
+            {
+                gl.tex_parameter_i32(texture_type.into_gl(), *parameter, *value as i32);
+            }
+        }
+
+        Ok(Self {
+            level,
+            texture_id,
+            size,
+            slot,
+            texture_type,
+        })
+    }
+
+    /// Sets whether the texture array is filtered linearly when it's scaled,
+    /// instead of keeping it's pixels sharp.
+    pub fn set_smooth(&self, gl: &Context, smooth: bool) {
+        let (min, mag) = if smooth {
+            (glow::LINEAR_MIPMAP_LINEAR, glow::LINEAR)
+        } else {
+            (glow::NEAREST_MIPMAP_LINEAR, glow::NEAREST)
+        };
+
+        unsafe {
+            gl.active_texture(glow::TEXTURE0 + self.slot);
+            gl.bind_texture(self.texture_type.into_gl(), self.texture_id);
+            let target = self.texture_type.into_gl();
+            gl.tex_parameter_i32(target, glow::TEXTURE_MIN_FILTER, min as i32);
+            gl.tex_parameter_i32(target, glow::TEXTURE_MAG_FILTER, mag as i32);
+        }
+    }
+
+    pub fn texture_id(&self) -> Option<TextureId> {
+        self.texture_id
+    }
+
     /// Loads an image into the texture array.
     ///
     /// # Panic
@@ -181,8 +260,13 @@ impl Texture {
     }
 
     pub fn bind(&self, gl: &Context) {
+        self.bind_to(gl, self.slot);
+    }
+
+    /// Binds the texture to a specific texture unit.
+    pub fn bind_to(&self, gl: &Context, slot: u32) {
         unsafe {
-            gl.active_texture(glow::TEXTURE0 + self.slot);
+            gl.active_texture(glow::TEXTURE0 + slot);
             assert!(self.texture_id.is_some());
             gl.bind_texture(self.texture_type.into_gl(), self.texture_id);
         }
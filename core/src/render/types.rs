@@ -15,6 +15,10 @@ pub type VertexArrayId = u32;
 pub type TextureId = u32;
 #[cfg(feature = "nat")]
 pub type UniformLocation = u32;
+#[cfg(feature = "nat")]
+pub type FramebufferId = u32;
+#[cfg(feature = "nat")]
+pub type RenderbufferId = u32;
 
 #[cfg(feature = "web")]
 pub type ShaderId = glow::WebShaderKey;
@@ -28,3 +32,7 @@ pub type VertexArrayId = glow::WebVertexArrayKey;
 pub type TextureId = glow::WebTextureKey;
 #[cfg(feature = "web")]
 pub type UniformLocation = web_sys::WebGlUniformLocation;
+#[cfg(feature = "web")]
+pub type FramebufferId = glow::WebFramebufferKey;
+#[cfg(feature = "web")]
+pub type RenderbufferId = glow::WebRenderbufferKey;
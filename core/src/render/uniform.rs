@@ -8,12 +8,24 @@ pub trait Uniform {
     unsafe fn set_as_uniform(&self, gl: &Context, location: Option<UniformLocation>);
 }
 
+impl Uniform for bool {
+    unsafe fn set_as_uniform(&self, gl: &Context, location: Option<UniformLocation>) {
+        gl.uniform_1_i32(location, *self as i32);
+    }
+}
+
 impl Uniform for f32 {
     unsafe fn set_as_uniform(&self, gl: &Context, location: Option<UniformLocation>) {
         gl.uniform_1_f32(location, *self);
     }
 }
 
+impl Uniform for vek::Vec2<f32> {
+    unsafe fn set_as_uniform(&self, gl: &Context, location: Option<UniformLocation>) {
+        gl.uniform_2_f32_slice(location, &self.into_array());
+    }
+}
+
 impl Uniform for vek::Vec3<f32> {
     unsafe fn set_as_uniform(&self, gl: &Context, location: Option<UniformLocation>) {
         gl.uniform_3_f32_slice(location, &self.into_array());
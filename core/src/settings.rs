@@ -0,0 +1,278 @@
+//! Window and graphics settings, which are read from a JSON file, and can be
+//! overridden with `--key=value` or `--key value` arguments.
+
+use crate::render::PostEffect;
+use crate::Error;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// File settings are read from, when it's not given with `--settings`.
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// How textures are sampled when they're scaled.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filtering {
+    /// Keeps pixels sharp.
+    Nearest,
+    Linear,
+}
+
+/// Resource of the settings the game was started with, or last reloaded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Size of the window, in logical pixels.
+    pub width: f64,
+    pub height: f64,
+
+    pub fullscreen: bool,
+    pub vsync: bool,
+
+    /// Samples per pixel of multisample anti-aliasing, where 0 disables it.
+    pub msaa: u16,
+
+    /// Most frames drawn per second, which isn't limited when `None`.
+    pub frame_cap: Option<f32>,
+
+    pub filtering: Filtering,
+
+    /// Scale of the user interface, on top of the scale factor of the screen.
+    pub ui_scale: f32,
+
+    /// Seed the world is generated from, which is random when `None`.
+    pub seed: Option<u64>,
+
+    /// Post-processing passes, applied in order.
+    pub post: Vec<PostEffect>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            width: 1024.0,
+            height: 768.0,
+            fullscreen: false,
+            vsync: true,
+            msaa: 0,
+            frame_cap: None,
+            filtering: Filtering::Nearest,
+            ui_scale: 1.0,
+            seed: None,
+            post: Vec::new(),
+        }
+    }
+}
+
+fn error(message: String) -> Error {
+    Error::SettingsError(message)
+}
+
+impl Settings {
+    /// Reads the settings file, which is skipped if it doesn't exist, and
+    /// overrides it with the given command line arguments.
+    #[cfg(feature = "nat")]
+    pub fn load(args: &[String]) -> Result<Self, Error> {
+        let mut path = SETTINGS_FILE.to_string();
+        let mut rest = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--settings" => {
+                    path = args
+                        .next()
+                        .ok_or_else(|| error("--settings expects a path".into()))?
+                        .clone();
+                }
+                _ if arg.starts_with("--settings=") => {
+                    path = arg["--settings=".len()..].into();
+                }
+                _ => rest.push(arg.clone()),
+            }
+        }
+
+        let file = match std::fs::read_to_string(&path) {
+            Ok(file) => Some(file),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(error(format!("{}: {}", path, err))),
+        };
+        Self::parse(file.as_deref().map(|file| (path.as_str(), file)), &rest)
+    }
+
+    /// Parses a settings file of `(name, contents)`, where missing keys keep
+    /// their default, and then applies the overrides of `args`.
+    pub fn parse(file: Option<(&str, &str)>, args: &[String]) -> Result<Self, Error> {
+        let settings = match file {
+            Some((name, contents)) => {
+                serde_json::from_str(contents).map_err(|err| error(format!("{}: {}", name, err)))?
+            }
+            None => Self::default(),
+        };
+
+        let mut values = match serde_json::to_value(settings) {
+            Ok(Value::Object(values)) => values,
+            _ => unreachable!("settings are a struct"),
+        };
+
+        for (key, value) in overrides(args)? {
+            match values.get_mut(&key) {
+                Some(slot) => *slot = value,
+                None => {
+                    let keys: Vec<_> = values.keys().map(|key| key.as_str()).collect();
+                    return Err(error(format!(
+                        "unknown setting `--{}`, expected one of {}",
+                        key,
+                        keys.join(", ")
+                    )));
+                }
+            }
+        }
+
+        let settings: Self = serde_json::from_value(Value::Object(values))
+            .map_err(|err| error(format!("command line: {}", err)))?;
+        settings.validate()
+    }
+
+    fn validate(self) -> Result<Self, Error> {
+        if self.width <= 0.0 || self.height <= 0.0 {
+            return Err(error("width and height must be positive".into()));
+        }
+        if self.msaa > 16 || !(self.msaa == 0 || self.msaa.is_power_of_two()) {
+            return Err(error(format!(
+                "msaa must be 0, 2, 4, 8 or 16, not {}",
+                self.msaa
+            )));
+        }
+        if matches!(self.frame_cap, Some(cap) if cap <= 0.0) {
+            return Err(error("frame_cap must be positive, or null".into()));
+        }
+        if self.ui_scale <= 0.0 {
+            return Err(error("ui_scale must be positive".into()));
+        }
+        for pass in &self.post {
+            let valid = match *pass {
+                PostEffect::Vignette { strength } => strength >= 0.0,
+                PostEffect::ColorGrading {
+                    exposure,
+                    contrast,
+                    saturation,
+                } => exposure >= 0.0 && contrast >= 0.0 && saturation >= 0.0,
+                PostEffect::Pixelate { size } => size >= 1.0,
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                } => threshold >= 0.0 && intensity >= 0.0,
+            };
+            if !valid {
+                return Err(error(format!("post has invalid parameters: {:?}", pass)));
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Splits arguments into `(key, value)` pairs, where dashes in keys stand for
+/// underscores, and values which aren't valid JSON are taken as strings.
+fn overrides(args: &[String]) -> Result<Vec<(String, Value)>, Error> {
+    let mut pairs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let arg = match arg.strip_prefix("--") {
+            Some(arg) => arg,
+            None => return Err(error(format!("unexpected argument `{}`", arg))),
+        };
+
+        let (key, value) = match arg.find('=') {
+            Some(i) => (&arg[..i], arg[i + 1..].to_string()),
+            None => match args.next() {
+                Some(value) => (arg, value.clone()),
+                None => return Err(error(format!("--{} expects a value", arg))),
+            },
+        };
+
+        let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+        pairs.push((key.replace('-', "_"), value));
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn message(result: Result<Settings, Error>) -> String {
+        match result {
+            Err(Error::SettingsError(message)) => message,
+            other => panic!("expected a settings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn override_test() {
+        let file = r#"{ "width": 800, "filtering": "linear", "frame_cap": 60 }"#;
+        let overrides = args(&["--vsync=false", "--frame-cap", "null", "--msaa", "4"]);
+        let settings = Settings::parse(Some(("test.json", file)), &overrides).unwrap();
+
+        assert_eq!(
+            settings,
+            Settings {
+                width: 800.0,
+                vsync: false,
+                msaa: 4,
+                frame_cap: None,
+                filtering: Filtering::Linear,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_key_test() {
+        let file = "{\n  \"vsnyc\": false\n}";
+        let file_error = message(Settings::parse(Some(("test.json", file)), &[]));
+        assert!(file_error.starts_with("test.json: unknown field `vsnyc`"));
+        assert!(file_error.contains("line 2"));
+
+        let arg_error = message(Settings::parse(None, &args(&["--vsnyc=false"])));
+        assert!(arg_error.starts_with("unknown setting `--vsnyc`"));
+
+        let value_error = message(Settings::parse(None, &args(&["--msaa=3"])));
+        assert!(value_error.contains("msaa"));
+    }
+
+    #[test]
+    fn post_test() {
+        let file = r#"{ "post": [
+            { "effect": "color_grading", "exposure": 1.2, "contrast": 1.0, "saturation": 0.8 },
+            { "effect": "vignette", "strength": 0.5 }
+        ] }"#;
+        let settings = Settings::parse(Some(("test.json", file)), &[]).unwrap();
+        assert_eq!(
+            settings.post,
+            vec![
+                PostEffect::ColorGrading {
+                    exposure: 1.2,
+                    contrast: 1.0,
+                    saturation: 0.8,
+                },
+                PostEffect::Vignette { strength: 0.5 },
+            ]
+        );
+
+        let overrides = args(&["--post", r#"[{ "effect": "pixelate", "size": 4 }]"#]);
+        let settings = Settings::parse(Some(("test.json", file)), &overrides).unwrap();
+        assert_eq!(settings.post, vec![PostEffect::Pixelate { size: 4.0 }]);
+
+        let file = r#"{ "post": [{ "effect": "vignette", "strenght": 0.5 }] }"#;
+        let unknown = message(Settings::parse(Some(("test.json", file)), &[]));
+        assert!(unknown.contains("unknown field `strenght`"));
+
+        let invalid = args(&["--post", r#"[{ "effect": "pixelate", "size": 0 }]"#]);
+        assert!(message(Settings::parse(None, &invalid)).starts_with("post has invalid"));
+    }
+}
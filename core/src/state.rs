@@ -1,12 +1,21 @@
 use crate::builder::BuilderSystem;
-use crate::components::*;
+use crate::construction::ConstructionSystem;
+use crate::conveyor::ConveyorSystem;
+use crate::crafting::FactorySystem;
+use crate::debug::DebugHudSystem;
+use crate::drill::DrillSystem;
+use crate::fluids::FluidSystem;
+use crate::health::DamageSystem;
+use crate::items::InventorySystem;
 use crate::loader::*;
 use crate::map::MappingSystem;
 use crate::physics::*;
+use crate::power::PowerSystem;
+use crate::profiler::{Category, Profiled, Profiler, WithProfiled};
+use crate::ui::UiSystem;
 
 use instant;
 use specs::prelude::*;
-use vek::*;
 
 /// Duration between current and last instants.
 #[derive(Default)]
@@ -37,16 +46,45 @@ impl GameState {
         world.insert(DeltaTime::default());
 
         let mut dispatcher = DispatcherBuilder::new()
-            .with(crate::logger::Sys::default(), "log-system", &[])
-            .with(MappingSystem::default(), "mapping-system", &[])
-            .with(AssetSystem::default(), "asset-system", &[])
-            .with(
+            .with_profiled(MappingSystem::default(), "mapping-system", &[])
+            .with_profiled(AssetSystem::default(), "asset-system", &[])
+            .with_profiled(UiSystem::default(), "ui-system", &[])
+            .with_profiled(
                 BuilderSystem::default(),
                 "builder-system",
+                &["mapping-system", "ui-system"],
+            )
+            .with_profiled(
+                ConstructionSystem::default(),
+                "construction-system",
+                &["builder-system"],
+            )
+            .with_profiled(DamageSystem::default(), "damage-system", &[])
+            .with_profiled(InventorySystem, "inventory-system", &[])
+            .with_profiled(
+                ConveyorSystem::default(),
+                "conveyor-system",
+                &["mapping-system", "inventory-system"],
+            )
+            .with_profiled(
+                DrillSystem,
+                "drill-system",
+                &["mapping-system", "conveyor-system"],
+            )
+            .with_profiled(PowerSystem::default(), "power-system", &["mapping-system"])
+            .with_profiled(FluidSystem::default(), "fluid-system", &["mapping-system"])
+            .with_profiled(
+                FactorySystem,
+                "factory-system",
+                &["conveyor-system", "power-system", "fluid-system"],
+            )
+            .with_profiled(PhysicSystem, "physic-system", &[])
+            .with_profiled(
+                DebugHudSystem::default(),
+                "debug-hud-system",
                 &["mapping-system"],
             )
-            .with(PhysicSystem, "physic-system", &[])
-            .with_thread_local(renderer)
+            .with_thread_local(Profiled::new("renderer", renderer))
             .build();
         dispatcher.setup(&mut world);
 
@@ -57,17 +95,6 @@ impl GameState {
         }
     }
 
-    pub fn create_block(&mut self, x: i32, y: i32, name: &str, red: f32) {
-        self.ecs
-            .create_entity()
-            .with(Name(name.into()))
-            .with(Coordinate(Vec2::new(x, y)))
-            .with(TextureIndex(None))
-            .with(Color(Rgba::new(1.0, 1.0, 1.0, 1.0)))
-            .with(Tile)
-            .build();
-    }
-
     pub fn tick(&mut self) {
         let now = instant::Instant::now();
         let duration = now.duration_since(self.last);
@@ -75,5 +102,9 @@ impl GameState {
         *self.ecs.write_resource::<DeltaTime>() = DeltaTime(duration);
         self.dis.dispatch(&self.ecs);
         self.ecs.maintain();
+
+        let mut profiler = self.ecs.write_resource::<Profiler>();
+        profiler.record("frame", Category::Frame, now, instant::Instant::now());
+        profiler.end_frame();
     }
 }
@@ -0,0 +1,386 @@
+//! Immediate-mode user interface, which is laid out and drawn from scratch
+//! every frame, with the sprites and text of the overlay.
+
+use crate::builder::BuildChoice;
+use crate::components::*;
+use crate::game::{Input, Scene};
+use crate::loader::Loader;
+use crate::render::Hud;
+
+use specs::prelude::*;
+use std::collections::HashMap;
+use vek::*;
+
+/// Height of a line of text, in pixels.
+const TEXT_SIZE: f32 = 16.0;
+
+/// Width of a character relative to it's height, which is the same for every
+/// character, because the font is monospace.
+const CHAR_WIDTH: f32 = 0.6;
+
+const PANEL: Rgba<f32> = Rgba {
+    r: 0.1,
+    g: 0.1,
+    b: 0.12,
+    a: 0.85,
+};
+const HOVERED: Rgba<f32> = Rgba {
+    r: 0.3,
+    g: 0.3,
+    b: 0.35,
+    a: 1.0,
+};
+const SELECTED: Rgba<f32> = Rgba {
+    r: 0.9,
+    g: 0.7,
+    b: 0.2,
+    a: 1.0,
+};
+
+/// State which widgets keep between frames.
+#[derive(Default)]
+pub struct UiState {
+    /// First visible row of every scroll list.
+    scroll: HashMap<&'static str, usize>,
+}
+
+/// Context widgets are drawn with, for a single frame, where bounds are in
+/// logical pixels from the top left of the screen.
+pub struct Ui<'a> {
+    state: &'a mut UiState,
+    hud: &'a mut Hud,
+
+    cursor: Vec2<f32>,
+    clicked: bool,
+    scroll: f32,
+
+    /// Whether the cursor is over any panel drawn so far.
+    hovered: bool,
+    tooltip: Option<String>,
+}
+
+impl<'a> Ui<'a> {
+    pub fn new(state: &'a mut UiState, hud: &'a mut Hud, input: &Input, cursor: Vec2<f32>) -> Self {
+        Self {
+            state,
+            hud,
+            cursor,
+            clicked: input.clicked,
+            scroll: input.scroll,
+            hovered: false,
+            tooltip: None,
+        }
+    }
+
+    fn contains(&self, bounds: Aabr<f32>) -> bool {
+        bounds.contains_point(self.cursor)
+    }
+
+    /// Draws a background, which takes input from the world below it.
+    pub fn panel(&mut self, bounds: Aabr<f32>) {
+        self.hovered |= self.contains(bounds);
+        self.hud.rect(bounds, PANEL);
+    }
+
+    pub fn label(&mut self, position: Vec2<f32>, text: &str) {
+        let text = Text::new(text, TEXT_SIZE);
+        self.hud.text(position, text, Rgba::white());
+    }
+
+    /// Draws a button with a centered label, and returns whether it was clicked.
+    pub fn button(&mut self, bounds: Aabr<f32>, label: &str) -> bool {
+        let hovered = self.contains(bounds);
+        self.hovered |= hovered;
+        let color = if hovered { HOVERED } else { PANEL };
+        self.hud.rect(bounds, color);
+
+        let center = bounds.center();
+        let mut text = Text::new(label, TEXT_SIZE);
+        text.align = crate::render::Align::Center;
+        self.hud.text(
+            Vec2::new(center.x, center.y - TEXT_SIZE / 2.0),
+            text,
+            Rgba::white(),
+        );
+
+        hovered && self.clicked
+    }
+
+    /// Draws a row of `(texture, name)` icons of `size` pixels, where hovering
+    /// an icon shows it's name, and returns the index of the clicked icon.
+    pub fn icon_grid(
+        &mut self,
+        position: Vec2<f32>,
+        size: f32,
+        icons: &[(u32, &str)],
+        selected: Option<usize>,
+    ) -> Option<usize> {
+        let mut clicked = None;
+        for (i, (texture, name)) in icons.iter().enumerate() {
+            let min = position + Vec2::new(i as f32 * (size + 4.0), 0.0);
+            let bounds = Aabr {
+                min,
+                max: min + size,
+            };
+
+            let hovered = self.contains(bounds);
+            self.hovered |= hovered;
+            if selected == Some(i) || hovered {
+                let color = if selected == Some(i) {
+                    SELECTED
+                } else {
+                    HOVERED
+                };
+                let border = Aabr {
+                    min: bounds.min - 2.0,
+                    max: bounds.max + 2.0,
+                };
+                self.hud.rect(border, color);
+            }
+            self.hud.sprite(bounds, *texture, Rgba::white());
+
+            if hovered {
+                self.tooltip(name);
+                if self.clicked {
+                    clicked = Some(i);
+                }
+            }
+        }
+        clicked
+    }
+
+    /// Shows text next to the cursor, over every widget.
+    pub fn tooltip(&mut self, text: &str) {
+        self.tooltip = Some(text.into());
+    }
+
+    /// Draws a list of items, which is scrolled with the wheel while hovered,
+    /// and returns the index of the clicked item.
+    pub fn scroll_list(
+        &mut self,
+        id: &'static str,
+        bounds: Aabr<f32>,
+        items: &[String],
+    ) -> Option<usize> {
+        self.panel(bounds);
+
+        let row = TEXT_SIZE + 4.0;
+        let visible = (bounds.size().h / row).floor().max(0.0) as usize;
+        let max_offset = items.len().saturating_sub(visible);
+
+        let hovered = self.contains(bounds);
+        let offset = self.state.scroll.entry(id).or_insert(0);
+        if hovered && self.scroll != 0.0 {
+            let scrolled = *offset as f32 - self.scroll.signum();
+            *offset = scrolled.max(0.0) as usize;
+        }
+        *offset = (*offset).min(max_offset);
+        let offset = *offset;
+
+        let mut clicked = None;
+        for (i, item) in items.iter().enumerate().skip(offset).take(visible) {
+            let min = bounds.min + Vec2::new(0.0, (i - offset) as f32 * row);
+            let item_bounds = Aabr {
+                min,
+                max: Vec2::new(bounds.max.x, min.y + row),
+            };
+
+            if self.contains(item_bounds) {
+                self.hud.rect(item_bounds, HOVERED);
+                if self.clicked {
+                    clicked = Some(i);
+                }
+            }
+            self.label(min + 2.0, item);
+        }
+        clicked
+    }
+
+    /// Draws the tooltip, and returns whether the cursor is over the interface,
+    /// in which case it's input shouldn't reach the world.
+    pub fn finish(self) -> bool {
+        // NOTE: text is drawn after every sprite, so the tooltip is only kept
+        // clear of other text by being placed below the cursor.
+        if let Some(tooltip) = self.tooltip {
+            let min = self.cursor + Vec2::new(16.0, 16.0);
+            let width = tooltip.chars().count() as f32 * TEXT_SIZE * CHAR_WIDTH;
+            let bounds = Aabr {
+                min: min - 4.0,
+                max: min + Vec2::new(width, TEXT_SIZE) + 4.0,
+            };
+            self.hud.rect(bounds, PANEL);
+            let text = Text::new(tooltip, TEXT_SIZE);
+            self.hud.text(min, text, Rgba::white());
+        }
+        self.hovered
+    }
+}
+
+/// System which draws the build menu and the list of placed blocks, and
+/// captures the input which lands on them.
+#[derive(Default)]
+pub struct UiSystem {
+    state: UiState,
+}
+
+impl<'a> System<'a> for UiSystem {
+    type SystemData = (
+        Read<'a, Scene>,
+        Read<'a, Loader>,
+        Write<'a, Input>,
+        Write<'a, Hud>,
+        Write<'a, BuildChoice>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Tile>,
+    );
+
+    fn run(
+        &mut self,
+        (scene, loader, mut input, mut hud, mut choice, names, tiles): Self::SystemData,
+    ) {
+        let screen = scene.logical_dimensions();
+        let cursor = scene.logical_cursor();
+        let mut ui = Ui::new(&mut self.state, &mut hud, &input, cursor);
+
+        // build menu, centered at the bottom of the screen
+        let icons: Vec<(u32, &str)> = loader
+            .names()
+            .map(|(index, name)| (index as u32, name))
+            .collect();
+        let icon = 48.0;
+        let width = icons.len() as f32 * (icon + 4.0) + icon + 16.0;
+        let min = Vec2::new((screen.x - width) / 2.0, screen.y - icon - 24.0);
+        ui.panel(Aabr {
+            min,
+            max: min + Vec2::new(width, icon + 16.0),
+        });
+
+        let selected = icons
+            .iter()
+            .position(|(_, name)| Some(*name) == choice.name.as_deref());
+        if let Some(i) = ui.icon_grid(min + 8.0, icon, &icons, selected) {
+            choice.name = Some(icons[i].1.to_string());
+        }
+        let degrees = choice.rotation.quarter_turns() * 90;
+        ui.label(
+            min - Vec2::new(0.0, TEXT_SIZE + 4.0),
+            &format!("rotation {}° (R)", degrees),
+        );
+
+        let clear = Vec2::new(min.x + width - icon - 8.0, min.y + 8.0);
+        let clear = Aabr {
+            min: clear,
+            max: clear + icon,
+        };
+        if ui.button(clear, "x") {
+            choice.name = None;
+        }
+
+        // placed blocks, by name
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for (name, _) in (&names, &tiles).join() {
+            match counts.iter_mut().find(|(n, _)| *n == name.0) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((name.0.clone(), 1)),
+            }
+        }
+        counts.sort();
+        let items: Vec<String> = counts
+            .iter()
+            .map(|(name, count)| format!("{} x{}", name, count))
+            .collect();
+
+        let min = Vec2::new(screen.x - 188.0, 8.0);
+        ui.label(min, "blocks");
+        let list = Aabr {
+            min: min + Vec2::new(0.0, TEXT_SIZE + 4.0),
+            max: min + Vec2::new(180.0, 160.0),
+        };
+        if let Some(i) = ui.scroll_list("blocks", list, &items) {
+            choice.name = Some(counts[i].0.clone());
+        }
+
+        input.captured = ui.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(clicked: bool, scroll: f32) -> Input {
+        Input {
+            clicked,
+            scroll,
+            ..Default::default()
+        }
+    }
+
+    fn bounds(min: f32, max: f32) -> Aabr<f32> {
+        Aabr {
+            min: Vec2::broadcast(min),
+            max: Vec2::broadcast(max),
+        }
+    }
+
+    #[test]
+    fn input_capture_test() {
+        let (mut state, mut hud) = Default::default();
+        let mut ui = Ui::new(
+            &mut state,
+            &mut hud,
+            &input(true, 0.0),
+            Vec2::broadcast(5.0),
+        );
+        assert!(ui.button(bounds(0.0, 10.0), "a"));
+        assert!(!ui.button(bounds(20.0, 30.0), "b"));
+        assert!(ui.finish());
+
+        let mut ui = Ui::new(
+            &mut state,
+            &mut hud,
+            &input(true, 0.0),
+            Vec2::broadcast(5.0),
+        );
+        assert_eq!(ui.icon_grid(Vec2::zero(), 10.0, &[(0, "a")], None), Some(0));
+        assert!(ui.finish());
+
+        let mut ui = Ui::new(
+            &mut state,
+            &mut hud,
+            &input(true, 0.0),
+            Vec2::broadcast(50.0),
+        );
+        assert!(!ui.button(bounds(0.0, 10.0), "a"));
+        assert!(!ui.finish());
+
+        let mut ui = Ui::new(
+            &mut state,
+            &mut hud,
+            &input(true, 0.0),
+            Vec2::broadcast(5.0),
+        );
+        ui.panel(bounds(0.0, 10.0));
+        assert!(ui.finish());
+    }
+
+    #[test]
+    fn scroll_list_test() {
+        let (mut state, mut hud) = Default::default();
+        let items: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        let list = bounds(0.0, 60.0);
+        let cursor = Vec2::new(10.0, 10.0);
+
+        // NOTE: rows are 20 pixels, so 3 rows are visible, and scrolling down
+        // stops once the last item is visible.
+        for _ in 0..10 {
+            let mut ui = Ui::new(&mut state, &mut hud, &input(false, -1.0), cursor);
+            ui.scroll_list("list", list, &items);
+        }
+        let mut ui = Ui::new(&mut state, &mut hud, &input(true, 0.0), cursor);
+        assert_eq!(ui.scroll_list("list", list, &items), Some(7));
+
+        let mut ui = Ui::new(&mut state, &mut hud, &input(true, 1.0), cursor);
+        assert_eq!(ui.scroll_list("list", list, &items), Some(6));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::render::Renderer;
+use crate::settings::Settings;
 use crate::Error;
 use std::convert::TryInto;
 
@@ -27,16 +28,31 @@ pub struct Window {
 }
 
 impl Window {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> Result<(Self, Renderer), Error> {
+    pub fn new(
+        event_loop: &winit::event_loop::EventLoop<()>,
+        settings: &Settings,
+    ) -> Result<(Self, Renderer), Error> {
         // initialize a native context with glutin
         #[cfg(feature = "nat")]
         {
+            use glutin::window::Fullscreen;
+            let fullscreen = if settings.fullscreen {
+                Some(Fullscreen::Borderless(event_loop.primary_monitor()))
+            } else {
+                None
+            };
             let window_builder = glutin::window::WindowBuilder::new()
                 .with_title("Minrusty")
-                .with_inner_size(glutin::dpi::LogicalSize::new(1024.0, 768.0));
+                .with_inner_size(glutin::dpi::LogicalSize::new(
+                    settings.width,
+                    settings.height,
+                ))
+                .with_fullscreen(fullscreen);
 
             let windowed_context = glutin::ContextBuilder::new()
-                .with_vsync(true)
+                .with_vsync(settings.vsync)
+                .with_multisampling(settings.msaa)
+                .with_depth_buffer(24)
                 .build_windowed(window_builder, &event_loop)
                 .unwrap();
             let windowed_context = unsafe { windowed_context.make_current().unwrap() };
@@ -63,8 +79,15 @@ impl Window {
                 #[derive(Serialize)]
                 struct Args {
                     alpha: bool,
+                    antialias: bool,
                 }
-                let args = Args { alpha: false };
+                // NOTE: alpha only decides if the canvas is composited with the
+                // page behind it, blending within the canvas is set per layer.
+                // The browser picks the number of samples when antialiasing.
+                let args = Args {
+                    alpha: false,
+                    antialias: settings.msaa > 0,
+                };
                 let args = JsValue::from_serde(&args).unwrap();
                 let web_gl_2 = canvas
                     .get_context_with_context_options("webgl2", &args)
@@ -109,10 +132,55 @@ impl Window {
         }
         #[cfg(feature = "nat")]
         {
-            unimplemented!();
+            self.windowed_context.window().scale_factor()
         }
     }
 
+    /// Resizes the surface drawn to, after the window was resized.
+    #[cfg_attr(feature = "web", allow(unused_variables))]
+    pub fn resize(&self, size: winit::dpi::PhysicalSize<u32>) {
+        // NOTE: the canvas is resized as it's dimensions are queried.
+        #[cfg(feature = "nat")]
+        self.windowed_context.resize(size);
+    }
+
+    /// Applies the settings which can change while the window is open, and
+    /// logs the ones which only apply after a restart.
+    pub fn apply(&self, old: &Settings, new: &Settings) {
+        // NOTE: on web, the size of the canvas is set by the page.
+        #[cfg(feature = "nat")]
+        {
+            if (old.width, old.height) != (new.width, new.height) {
+                let size = winit::dpi::LogicalSize::new(new.width, new.height);
+                self.winit_window().set_inner_size(size);
+            }
+        }
+        if old.fullscreen != new.fullscreen {
+            self.set_fullscreen(new.fullscreen);
+        }
+        if (old.vsync, old.msaa) != (new.vsync, new.msaa) {
+            crate::logger::log("vsync and msaa settings apply after a restart");
+        }
+    }
+
+    /// Switches between borderless fullscreen on the current monitor, and
+    /// windowed mode.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        use winit::window::Fullscreen;
+        let window = self.winit_window();
+        if fullscreen {
+            let monitor = window.current_monitor();
+            window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+        } else {
+            window.set_fullscreen(None);
+        }
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        let fullscreen = self.winit_window().fullscreen().is_some();
+        self.set_fullscreen(!fullscreen);
+    }
+
     pub fn dimensions(&self) -> (i32, i32) {
         use winit::dpi::*;
         #[cfg(feature = "web")]
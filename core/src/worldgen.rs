@@ -0,0 +1,298 @@
+//! Seeded world generation, where terrain is decided by layers of noise which
+//! only depend on the seed and the coordinate, so that the same seed always
+//! generates the same world, chunk by chunk.
+
+use crate::components::*;
+use crate::loader::Loader;
+use crate::map::{chunk_coordinate, Cell, Map, CHUNK_SIZE};
+use crate::render::{Layer, WHITE};
+
+use specs::prelude::*;
+use vek::*;
+
+/// Texture walls are drawn with.
+const WALL_TEXTURE: &str = "c";
+
+/// Kind of ground a tile is on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Floor {
+    #[default]
+    Grass,
+    Sand,
+    Stone,
+}
+
+impl Floor {
+    pub fn color(self) -> Rgba<f32> {
+        match self {
+            Floor::Grass => Rgba::new(0.35, 0.5, 0.3, 1.0),
+            Floor::Sand => Rgba::new(0.75, 0.68, 0.5, 1.0),
+            Floor::Stone => Rgba::new(0.45, 0.45, 0.48, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Ore {
+    Copper,
+    Iron,
+}
+
+impl Ore {
+    pub fn color(self) -> Rgba<f32> {
+        match self {
+            Ore::Copper => Rgba::new(0.8, 0.5, 0.3, 1.0),
+            Ore::Iron => Rgba::new(0.65, 0.6, 0.65, 1.0),
+        }
+    }
+
+    /// Name of the item the ore is mined as.
+    pub fn item(self) -> &'static str {
+        match self {
+            Ore::Copper => "copper-ore",
+            Ore::Iron => "iron-ore",
+        }
+    }
+
+    /// Lowest tier of drill which mines the ore.
+    pub fn hardness(self) -> u32 {
+        match self {
+            Ore::Copper => 1,
+            Ore::Iron => 2,
+        }
+    }
+}
+
+/// What the world is made of at a coordinate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Terrain {
+    pub floor: Floor,
+
+    /// Ore under the floor, which is never under walls.
+    pub ore: Option<Ore>,
+
+    pub wall: bool,
+}
+
+/// Layers of noise, which are seeded separately.
+#[derive(Clone, Copy)]
+enum Noise {
+    Elevation = 1,
+    Moisture,
+    Copper,
+    Iron,
+}
+
+/// Mixes a seed with a lattice point into a well distributed number, with
+/// the finalizer of splitmix64.
+fn hash(seed: u64, x: i32, y: i32) -> u64 {
+    let mut h = seed
+        ^ u64::from(x as u32).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ u64::from(y as u32).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// Value between 0 and 1 at a lattice point.
+fn lattice(seed: u64, x: i32, y: i32) -> f32 {
+    (hash(seed, x, y) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Value noise, which smoothly interpolates between random values at every
+/// integer point.
+fn value_noise(seed: u64, point: Vec2<f32>) -> f32 {
+    let cell = point.floor();
+    let t = point - cell;
+    let t = t * t * (Vec2::broadcast(3.0) - t * 2.0);
+    let (x, y) = (cell.x as i32, cell.y as i32);
+
+    let bottom = Lerp::lerp(lattice(seed, x, y), lattice(seed, x + 1, y), t.x);
+    let top = Lerp::lerp(lattice(seed, x, y + 1), lattice(seed, x + 1, y + 1), t.x);
+    Lerp::lerp(bottom, top, t.y)
+}
+
+/// Octaves of value noise, each with half the scale and weight of the one
+/// before it, normalized between 0 and 1.
+fn fractal_noise(seed: u64, point: Vec2<f32>, octaves: u32) -> f32 {
+    let (mut total, mut weights) = (0.0, 0.0);
+    let (mut weight, mut frequency) = (1.0, 1.0);
+    for octave in 0..octaves {
+        let seed = hash(seed, octave as i32, 0);
+        total += value_noise(seed, point * frequency) * weight;
+        weights += weight;
+        weight *= 0.5;
+        frequency *= 2.0;
+    }
+    total / weights
+}
+
+/// Generator of the terrain of a world.
+#[derive(Clone, Copy, Debug)]
+pub struct WorldGen {
+    seed: u64,
+}
+
+impl WorldGen {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Noise of a layer, where `scale` is roughly the size of it's features in tiles.
+    fn noise(&self, noise: Noise, coordinate: Vec2<i32>, scale: f32, octaves: u32) -> f32 {
+        let seed = hash(self.seed, noise as i32, 0);
+        let point = coordinate.numcast::<f32>().unwrap() / scale;
+        fractal_noise(seed, point, octaves)
+    }
+
+    pub fn terrain(&self, coordinate: Vec2<i32>) -> Terrain {
+        let elevation = self.noise(Noise::Elevation, coordinate, 12.0, 4);
+        let moisture = self.noise(Noise::Moisture, coordinate, 20.0, 3);
+
+        let wall = elevation > 0.65;
+        let floor = match (elevation, moisture) {
+            (e, _) if e > 0.58 => Floor::Stone,
+            (_, m) if m < 0.42 => Floor::Sand,
+            _ => Floor::Grass,
+        };
+
+        let ore = if wall {
+            None
+        } else if self.noise(Noise::Copper, coordinate, 4.0, 2) > 0.72 {
+            Some(Ore::Copper)
+        } else if floor == Floor::Stone && self.noise(Noise::Iron, coordinate, 4.0, 2) > 0.66 {
+            Some(Ore::Iron)
+        } else {
+            None
+        };
+
+        Terrain { floor, ore, wall }
+    }
+
+    /// Terrain of every tile of a chunk, row by row.
+    pub fn chunk(&self, chunk: Vec2<i32>) -> impl Iterator<Item = (Vec2<i32>, Terrain)> + '_ {
+        let origin = chunk * CHUNK_SIZE;
+        (0..CHUNK_SIZE).flat_map(move |y| {
+            (0..CHUNK_SIZE).map(move |x| {
+                let coordinate = origin + Vec2::new(x, y);
+                (coordinate, self.terrain(coordinate))
+            })
+        })
+    }
+}
+
+/// Spawns the floors and walls of every chunk of the map, where floors are
+/// colored by their ore, if they have any, and keeps the floor and ore of
+/// every tile in the map.
+pub fn spawn(world: &mut World, generator: &WorldGen) {
+    let dimensions = world.read_resource::<Map<Cell>>().dimensions();
+    let wall_texture = world
+        .read_resource::<Loader>()
+        .names()
+        .find(|(_, name)| *name == WALL_TEXTURE)
+        .map(|(index, _)| index as u32);
+
+    let last = chunk_coordinate(dimensions - 1);
+    for chunk_y in 0..=last.y {
+        for chunk_x in 0..=last.x {
+            let chunk = Vec2::new(chunk_x, chunk_y);
+            for (coordinate, terrain) in generator.chunk(chunk) {
+                if coordinate.x >= dimensions.x || coordinate.y >= dimensions.y {
+                    continue;
+                }
+
+                if let Some(cell) = world.write_resource::<Map<Cell>>().get_mut(coordinate) {
+                    cell.floor = terrain.floor;
+                    cell.ore = terrain.ore;
+                }
+
+                let color = terrain.ore.map_or(terrain.floor.color(), Ore::color);
+                world
+                    .create_entity()
+                    .with(Coordinate(coordinate))
+                    .with(TextureIndex(Some(WHITE)))
+                    .with(Color(color))
+                    .with(RenderLayer(Layer::Floor))
+                    .build();
+
+                if terrain.wall {
+                    world
+                        .create_entity()
+                        .with(Name("wall".into()))
+                        .with(Coordinate(coordinate))
+                        .with(TextureIndex(wall_texture))
+                        .with(Color(Rgba::new(0.7, 0.7, 0.75, 1.0)))
+                        .with(Tile)
+                        .build();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FNV-1a hash of the terrain of a region, which unlike the standard
+    /// hasher is guaranteed to stay the same between versions of Rust.
+    fn region_hash(generator: &WorldGen, min: Vec2<i32>, size: i32) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        for y in min.y..min.y + size {
+            for x in min.x..min.x + size {
+                let terrain = generator.terrain(Vec2::new(x, y));
+                let byte = terrain.floor as u8
+                    | (terrain.ore.map_or(0, |ore| ore as u8 + 1) << 2)
+                    | ((terrain.wall as u8) << 4);
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        hash
+    }
+
+    #[test]
+    fn seed_test() {
+        let region = |seed, min| region_hash(&WorldGen::new(seed), min, 64);
+        let origin = Vec2::zero();
+        let far = Vec2::new(-1000, 5000);
+
+        assert_eq!(region(1, origin), region(1, origin));
+        assert_eq!(region(1, far), region(1, far));
+        assert_ne!(region(1, origin), region(2, origin));
+        assert_ne!(region(1, origin), region(1, far));
+
+        // NOTE: this changes whenever generation does, which breaks every
+        // seed players have shared, so it should only be updated on purpose.
+        assert_eq!(region(42, origin), 9584017801066101056);
+    }
+
+    #[test]
+    fn chunk_test() {
+        let generator = WorldGen::new(7);
+        let tiles: Vec<_> = generator.chunk(Vec2::new(-1, 2)).collect();
+        assert_eq!(tiles.len(), (CHUNK_SIZE * CHUNK_SIZE) as usize);
+        assert_eq!(tiles[0].0, Vec2::new(-CHUNK_SIZE, 2 * CHUNK_SIZE));
+        for (coordinate, terrain) in tiles {
+            assert_eq!(generator.terrain(coordinate), terrain);
+        }
+    }
+
+    #[test]
+    fn terrain_test() {
+        let generator = WorldGen::new(42);
+        let mut walls = 0;
+        let mut ores = 0;
+        for y in 0..128 {
+            for x in 0..128 {
+                let terrain = generator.terrain(Vec2::new(x, y));
+                walls += terrain.wall as usize;
+                ores += terrain.ore.is_some() as usize;
+                assert!(!(terrain.wall && terrain.ore.is_some()));
+            }
+        }
+        assert!(walls > 0 && walls < 128 * 128 / 4);
+        assert!(ores > 0 && ores < 128 * 128 / 4);
+    }
+}